@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::Deserialize;
+
+use crate::assets::Assets;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<AnimationLibrary>::new(&["animations.ron"]))
+            .add_message::<AnimationFinished>()
+            .add_systems(Update, (animate, despawn_finished_animations).chain());
+    }
+}
+
+/// Named animation clips loaded from `animations.ron`.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct AnimationLibrary {
+    clips: HashMap<String, AnimationClip>,
+}
+
+/// Looks up clips from the [`AnimationLibrary`] loaded with the game [`Assets`].
+#[derive(SystemParam)]
+pub struct AnimationClips<'w> {
+    assets: Res<'w, Assets>,
+    libraries: Res<'w, bevy::asset::Assets<AnimationLibrary>>,
+}
+
+impl AnimationClips<'_> {
+    pub fn get(&self, name: &str) -> AnimationClip {
+        self.libraries
+            .get(&self.assets.animations)
+            .and_then(|library| library.clips.get(name))
+            .cloned()
+            .unwrap_or_else(|| {
+                warn!("Missing animation clip {name}");
+                AnimationClip::default()
+            })
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum AnimationMode {
+    #[default]
+    Loop,
+    Once,
+    PingPong,
+}
+
+/// Plays a run of consecutive texture atlas frames on the entity's [`Sprite`].
+#[derive(Component, Deserialize, Clone)]
+pub struct AnimationClip {
+    first: usize,
+    frames: usize,
+    /// How long each frame is shown, in seconds. The last duration is reused for any frames
+    /// past the end of the list.
+    frame_durations: Vec<f32>,
+    #[serde(default)]
+    mode: AnimationMode,
+    #[serde(skip)]
+    frame: usize,
+    #[serde(skip)]
+    elapsed: f32,
+    #[serde(skip)]
+    reversed: bool,
+    #[serde(skip)]
+    finished: bool,
+}
+
+impl Default for AnimationClip {
+    fn default() -> Self {
+        Self {
+            first: 0,
+            frames: 1,
+            frame_durations: vec![1.],
+            mode: AnimationMode::Loop,
+            frame: 0,
+            elapsed: 0.,
+            reversed: false,
+            finished: false,
+        }
+    }
+}
+
+impl AnimationClip {
+    /// Atlas index of the frame currently shown.
+    pub fn index(&self) -> usize {
+        self.first + self.frame
+    }
+
+    fn frame_duration(&self) -> f32 {
+        self.frame_durations
+            .get(self.frame)
+            .or(self.frame_durations.last())
+            .copied()
+            .unwrap_or(1.)
+            .max(0.001)
+    }
+
+    /// Moves to the next frame, returning `false` once a one-shot clip has run out of frames.
+    fn advance(&mut self) -> bool {
+        let last = self.frames.saturating_sub(1);
+        match self.mode {
+            AnimationMode::Loop => {
+                self.frame = if self.frame >= last {
+                    0
+                } else {
+                    self.frame + 1
+                }
+            }
+            AnimationMode::Once => {
+                if self.frame >= last {
+                    self.finished = true;
+                    return false;
+                }
+                self.frame += 1;
+            }
+            AnimationMode::PingPong => {
+                if last == 0 {
+                    return true;
+                }
+                if self.frame == last {
+                    self.reversed = true;
+                } else if self.frame == 0 {
+                    self.reversed = false;
+                }
+                self.frame = if self.reversed {
+                    self.frame - 1
+                } else {
+                    self.frame + 1
+                };
+            }
+        }
+        true
+    }
+}
+
+/// Sent when an [`AnimationMode::Once`] clip has shown its last frame.
+#[derive(Message)]
+pub struct AnimationFinished {
+    pub entity: Entity,
+}
+
+/// Despawns the entity once its [`AnimationClip`] has finished playing.
+#[derive(Component)]
+pub struct DespawnOnFinish;
+
+fn animate(
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut AnimationClip, &mut Sprite)>,
+    mut finished_messages: MessageWriter<AnimationFinished>,
+) {
+    for (entity, mut clip, mut sprite) in &mut query {
+        if clip.finished {
+            continue;
+        }
+        clip.elapsed += time.delta_secs();
+        let mut advanced = false;
+        while clip.elapsed >= clip.frame_duration() {
+            clip.elapsed -= clip.frame_duration();
+            if !clip.advance() {
+                finished_messages.write(AnimationFinished { entity });
+                break;
+            }
+            advanced = true;
+        }
+        if advanced && let Some(atlas) = &mut sprite.texture_atlas {
+            atlas.index = clip.index();
+        }
+    }
+}
+
+fn despawn_finished_animations(
+    mut commands: Commands,
+    mut finished_messages: MessageReader<AnimationFinished>,
+    query: Query<(), With<DespawnOnFinish>>,
+) {
+    for msg in finished_messages.read() {
+        if query.contains(msg.entity) {
+            commands.entity(msg.entity).despawn();
+        }
+    }
+}
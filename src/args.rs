@@ -0,0 +1,52 @@
+//! Launch options given as `--name value` command line flags on native builds and as
+//! `?name=value` URL query parameters on the web.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get(name: &str) -> Option<String> {
+    let flag = format!("--{name}");
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            // A flag followed by another flag has no value.
+            return args.next().filter(|value| !value.starts_with("--"));
+        }
+        if let Some(value) = arg
+            .strip_prefix(&flag)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn get(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get(name)
+}
+
+/// Whether the option was given at all, with or without a value.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn has(name: &str) -> bool {
+    let flag = format!("--{name}");
+    std::env::args().skip(1).any(|arg| {
+        arg.strip_prefix(&flag)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('='))
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn has(name: &str) -> bool {
+    web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .and_then(|search| web_sys::UrlSearchParams::new_with_str(&search).ok())
+        .is_some_and(|params| params.has(name))
+}
+
+/// Parses the option as `T`, ignoring it if it doesn't parse.
+pub fn parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    get(name)?.parse().ok()
+}
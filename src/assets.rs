@@ -1,6 +1,11 @@
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 
+use crate::{
+    animation::AnimationLibrary, cake::CakeDefinition, calendar::Calendar, credit::CreditTerms,
+    economy::RecipeBook, gamba::fishing::FishTable, rules::OddsTable,
+};
+
 #[derive(AssetCollection, Resource)]
 pub struct Assets {
     #[asset(texture_atlas_layout(tile_size_x = 32, tile_size_y = 32, columns = 10, rows = 10))]
@@ -11,6 +16,32 @@ pub struct Assets {
     pub textures: Handle<Image>,
     #[asset(path = "pond.png")]
     pub pond: Handle<Image>,
+    #[asset(path = "animations.ron")]
+    pub animations: Handle<AnimationLibrary>,
+    #[asset(path = "cakes/birthday.cake.ron")]
+    pub cake: Handle<CakeDefinition>,
+    /// Every cake the calendar can ask for, loaded up front so switching between them is instant.
+    /// Only held to keep them loaded, the calendar looks them up by path.
+    #[allow(dead_code)]
+    #[asset(
+        paths(
+            "cakes/birthday.cake.ron",
+            "cakes/rings.cake.ron",
+            "cakes/trick.cake.ron"
+        ),
+        collection(typed)
+    )]
+    pub cakes: Vec<Handle<CakeDefinition>>,
+    #[asset(path = "team.calendar.ron")]
+    pub calendar: Handle<Calendar>,
+    #[asset(path = "cakes/bakery.recipes.ron")]
+    pub recipes: Handle<RecipeBook>,
+    #[asset(path = "gamba.odds.ron")]
+    pub odds: Handle<OddsTable>,
+    #[asset(path = "gamba.credit.ron")]
+    pub credit_terms: Handle<CreditTerms>,
+    #[asset(path = "pond.fish.ron")]
+    pub fish: Handle<FishTable>,
     #[asset(path = "eating_sound.ogg")]
     pub eating_sound: Handle<AudioSource>,
     #[asset(path = "pickle_mew.ogg")]
@@ -0,0 +1,421 @@
+//! Plays the gamba for many rounds without rendering anything and reports how each betting
+//! strategy fared.
+//!
+//! ```text
+//! cargo run --release --bin gamba-sim -- --runs 1000 --rounds 1000 --format json
+//! ```
+//!
+//! Options: `--strategy NAME` (default: all of them), `--runs N`, `--rounds N`, `--score N`
+//! (starting money), `--base-bet N`, `--bet side|exact|column|pair|parity`, `--seed N`,
+//! `--odds PATH`, `--format csv|json` and `--output PATH` (default: stdout).
+
+// The game uses more of the options and the rules than the simulator does.
+#[allow(dead_code)]
+#[path = "../args.rs"]
+mod args;
+#[allow(dead_code)]
+#[path = "../rules.rs"]
+mod rules;
+
+use bevy_rand::prelude::WyRand;
+use rand_core::{RngCore, SeedableRng};
+use serde::Serialize;
+
+use rules::{BetKind, OddsTable, Outcome, Parity, Settlement, Side, settle};
+
+const DEFAULT_ODDS: &str = "assets/gamba.odds.ron";
+
+/// Decides how much to stake each round.
+trait Strategy {
+    fn name(&self) -> &'static str;
+    /// Forgets everything from the previous run.
+    fn reset(&mut self);
+    /// Stake for the next round, before it is clamped to the score.
+    fn next_bet(&mut self, base: u64, score: u64) -> u64;
+    fn settled(&mut self, settlement: Settlement);
+}
+
+/// Always stakes the base bet.
+struct Flat;
+
+impl Strategy for Flat {
+    fn name(&self) -> &'static str {
+        "flat"
+    }
+
+    fn reset(&mut self) {}
+
+    fn next_bet(&mut self, base: u64, _score: u64) -> u64 {
+        base
+    }
+
+    fn settled(&mut self, _settlement: Settlement) {}
+}
+
+/// Doubles the stake after every loss and goes back to the base bet after a win.
+struct Martingale {
+    multiplier: u64,
+}
+
+impl Strategy for Martingale {
+    fn name(&self) -> &'static str {
+        "martingale"
+    }
+
+    fn reset(&mut self) {
+        self.multiplier = 1;
+    }
+
+    fn next_bet(&mut self, base: u64, _score: u64) -> u64 {
+        base.saturating_mul(self.multiplier)
+    }
+
+    fn settled(&mut self, settlement: Settlement) {
+        self.multiplier = match settlement {
+            Settlement::Lost => self.multiplier.saturating_mul(2),
+            Settlement::Won | Settlement::Bankrupt => 1,
+        };
+    }
+}
+
+/// Walks one step up the Fibonacci sequence after a loss and two steps down after a win.
+struct Fibonacci {
+    step: usize,
+}
+
+impl Strategy for Fibonacci {
+    fn name(&self) -> &'static str {
+        "fibonacci"
+    }
+
+    fn reset(&mut self) {
+        self.step = 0;
+    }
+
+    fn next_bet(&mut self, base: u64, _score: u64) -> u64 {
+        let (mut a, mut b) = (1u64, 1u64);
+        for _ in 0..self.step {
+            (a, b) = (b, a.saturating_add(b));
+        }
+        base.saturating_mul(a)
+    }
+
+    fn settled(&mut self, settlement: Settlement) {
+        self.step = match settlement {
+            Settlement::Won => self.step.saturating_sub(2),
+            Settlement::Lost => self.step + 1,
+            Settlement::Bankrupt => 0,
+        };
+    }
+}
+
+/// Adds one base bet to the stake after a loss and takes one away after a win.
+struct DAlembert {
+    units: u64,
+}
+
+impl Strategy for DAlembert {
+    fn name(&self) -> &'static str {
+        "dalembert"
+    }
+
+    fn reset(&mut self) {
+        self.units = 1;
+    }
+
+    fn next_bet(&mut self, base: u64, _score: u64) -> u64 {
+        base.saturating_mul(self.units)
+    }
+
+    fn settled(&mut self, settlement: Settlement) {
+        self.units = match settlement {
+            Settlement::Won => (self.units - 1).max(1),
+            Settlement::Lost => self.units + 1,
+            Settlement::Bankrupt => 1,
+        };
+    }
+}
+
+/// Stakes everything, every round.
+struct AllIn;
+
+impl Strategy for AllIn {
+    fn name(&self) -> &'static str {
+        "all-in"
+    }
+
+    fn reset(&mut self) {}
+
+    fn next_bet(&mut self, _base: u64, score: u64) -> u64 {
+        score
+    }
+
+    fn settled(&mut self, _settlement: Settlement) {}
+}
+
+fn strategies() -> Vec<Box<dyn Strategy>> {
+    vec![
+        Box::new(Flat),
+        Box::new(Martingale { multiplier: 1 }),
+        Box::new(Fibonacci { step: 0 }),
+        Box::new(DAlembert { units: 1 }),
+        Box::new(AllIn),
+    ]
+}
+
+struct Config {
+    runs: u64,
+    rounds: u64,
+    score: u64,
+    base_bet: u64,
+    bet: BetKind,
+    seed: u64,
+    odds: OddsTable,
+    format: Format,
+}
+
+enum Format {
+    Csv,
+    Json,
+}
+
+struct RunResult {
+    final_score: u64,
+    bankruptcies: u64,
+    max_drawdown: u64,
+    longest_win_streak: u64,
+    longest_loss_streak: u64,
+}
+
+#[derive(Serialize)]
+struct Percentiles {
+    min: u64,
+    p10: u64,
+    p25: u64,
+    median: u64,
+    p75: u64,
+    p90: u64,
+    max: u64,
+}
+
+#[derive(Serialize)]
+struct Report {
+    strategy: &'static str,
+    runs: u64,
+    rounds: u64,
+    mean_final_score: f64,
+    final_score: Percentiles,
+    bankruptcies_per_run: f64,
+    /// Fraction of runs that went broke at least once.
+    runs_with_bankruptcy: f64,
+    mean_max_drawdown: f64,
+    max_drawdown: u64,
+    longest_win_streak: u64,
+    longest_loss_streak: u64,
+}
+
+fn run(strategy: &mut dyn Strategy, config: &Config, rng: &mut WyRand) -> RunResult {
+    strategy.reset();
+    let mut score = config.score;
+    let mut result = RunResult {
+        final_score: 0,
+        bankruptcies: 0,
+        max_drawdown: 0,
+        longest_win_streak: 0,
+        longest_loss_streak: 0,
+    };
+    let mut peak = score;
+    let (mut wins, mut losses) = (0, 0);
+    for _ in 0..config.rounds {
+        let mut bet = strategy.next_bet(config.base_bet, score).clamp(1, score);
+        let outcome = Outcome::from_roll(rng.next_u32());
+        let settlement = settle(
+            &mut score,
+            &mut bet,
+            &config.bet,
+            &outcome,
+            &config.odds,
+            true,
+            rng,
+        );
+        strategy.settled(settlement);
+        match settlement {
+            Settlement::Won => {
+                wins += 1;
+                losses = 0;
+            }
+            Settlement::Lost | Settlement::Bankrupt => {
+                wins = 0;
+                losses += 1;
+            }
+        }
+        if settlement == Settlement::Bankrupt {
+            result.bankruptcies += 1;
+        }
+        peak = peak.max(score);
+        result.max_drawdown = result.max_drawdown.max(peak - score);
+        result.longest_win_streak = result.longest_win_streak.max(wins);
+        result.longest_loss_streak = result.longest_loss_streak.max(losses);
+    }
+    result.final_score = score;
+    result
+}
+
+fn simulate(strategy: &mut dyn Strategy, config: &Config) -> Report {
+    let mut rng = WyRand::seed_from_u64(config.seed);
+    let results: Vec<RunResult> = (0..config.runs)
+        .map(|_| run(strategy, config, &mut rng))
+        .collect();
+
+    let mut final_scores: Vec<u64> = results.iter().map(|r| r.final_score).collect();
+    final_scores.sort_unstable();
+    let percentile = |p: usize| final_scores[(final_scores.len() - 1) * p / 100];
+    let runs = results.len() as f64;
+    let mean =
+        |value: fn(&RunResult) -> u64| results.iter().map(|r| value(r) as f64).sum::<f64>() / runs;
+
+    Report {
+        strategy: strategy.name(),
+        runs: config.runs,
+        rounds: config.rounds,
+        mean_final_score: mean(|r| r.final_score),
+        final_score: Percentiles {
+            min: percentile(0),
+            p10: percentile(10),
+            p25: percentile(25),
+            median: percentile(50),
+            p75: percentile(75),
+            p90: percentile(90),
+            max: percentile(100),
+        },
+        bankruptcies_per_run: mean(|r| r.bankruptcies),
+        runs_with_bankruptcy: results.iter().filter(|r| r.bankruptcies > 0).count() as f64 / runs,
+        mean_max_drawdown: mean(|r| r.max_drawdown),
+        max_drawdown: results.iter().map(|r| r.max_drawdown).max().unwrap_or(0),
+        longest_win_streak: results
+            .iter()
+            .map(|r| r.longest_win_streak)
+            .max()
+            .unwrap_or(0),
+        longest_loss_streak: results
+            .iter()
+            .map(|r| r.longest_loss_streak)
+            .max()
+            .unwrap_or(0),
+    }
+}
+
+fn to_csv(reports: &[Report]) -> String {
+    let mut csv = String::from(
+        "strategy,runs,rounds,mean_final_score,min,p10,p25,median,p75,p90,max,\
+         bankruptcies_per_run,runs_with_bankruptcy,mean_max_drawdown,max_drawdown,\
+         longest_win_streak,longest_loss_streak\n",
+    );
+    for report in reports {
+        let scores = &report.final_score;
+        csv += &format!(
+            "{},{},{},{:.2},{},{},{},{},{},{},{},{:.4},{:.4},{:.2},{},{},{}\n",
+            report.strategy,
+            report.runs,
+            report.rounds,
+            report.mean_final_score,
+            scores.min,
+            scores.p10,
+            scores.p25,
+            scores.median,
+            scores.p75,
+            scores.p90,
+            scores.max,
+            report.bankruptcies_per_run,
+            report.runs_with_bankruptcy,
+            report.mean_max_drawdown,
+            report.max_drawdown,
+            report.longest_win_streak,
+            report.longest_loss_streak,
+        );
+    }
+    csv
+}
+
+fn parse_bet(name: &str) -> Result<BetKind, String> {
+    Ok(match name {
+        "side" => BetKind::Side(Side::Left),
+        "exact" => BetKind::Exact {
+            side: Side::Left,
+            column: 0,
+        },
+        "column" => BetKind::Column(0),
+        "pair" => BetKind::AdjacentPair {
+            side: Side::Left,
+            first: 0,
+        },
+        "parity" => BetKind::Parity(Parity::Odd),
+        _ => return Err(format!("unknown bet kind {name}")),
+    })
+}
+
+fn parse_format(name: &str) -> Result<Format, String> {
+    match name {
+        "csv" => Ok(Format::Csv),
+        "json" => Ok(Format::Json),
+        _ => Err(format!("unknown format {name}, expected csv or json")),
+    }
+}
+
+fn config() -> Result<Config, String> {
+    let odds_path = args::get("odds").unwrap_or_else(|| DEFAULT_ODDS.into());
+    let odds = std::fs::read_to_string(&odds_path)
+        .map_err(|e| format!("failed to read {odds_path}: {e}"))?;
+    let odds = ron::from_str(&odds).map_err(|e| format!("failed to parse {odds_path}: {e}"))?;
+    let seed = args::parse("seed").unwrap_or_else(|| rand::rng().next_u64());
+    eprintln!("Seed: {seed}");
+    Ok(Config {
+        runs: args::parse("runs").unwrap_or(1000).max(1),
+        rounds: args::parse("rounds").unwrap_or(1000),
+        score: args::parse("score").unwrap_or(100).max(1),
+        base_bet: args::parse("base-bet").unwrap_or(1).max(1),
+        bet: parse_bet(&args::get("bet").unwrap_or_else(|| "side".into()))?,
+        seed,
+        odds,
+        format: parse_format(&args::get("format").unwrap_or_else(|| "csv".into()))?,
+    })
+}
+
+fn main() {
+    let config = match config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let strategy = args::get("strategy");
+    let mut reports = Vec::new();
+    for mut candidate in strategies() {
+        if strategy
+            .as_deref()
+            .is_none_or(|name| name == candidate.name())
+        {
+            reports.push(simulate(candidate.as_mut(), &config));
+        }
+    }
+    if reports.is_empty() {
+        eprintln!("unknown strategy {}", strategy.unwrap_or_default());
+        std::process::exit(1);
+    }
+
+    let output = match config.format {
+        Format::Json => serde_json::to_string_pretty(&reports).expect("reports serialize"),
+        Format::Csv => to_csv(&reports),
+    };
+    match args::get("output") {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, output) {
+                eprintln!("failed to write {path}: {e}");
+                std::process::exit(1);
+            }
+        }
+        None => print!("{output}"),
+    }
+}
@@ -1,22 +1,56 @@
 use bevy::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
 use bevy_light_2d::prelude::*;
 use bevy_rand::prelude::*;
 use rand_core::RngCore;
+use serde::Deserialize;
 
 use crate::{
     GameState,
+    animation::{AnimationClips, DespawnOnFinish},
     assets::Assets,
-    util::{Animation, animate, despawn_all},
+    calendar::{CalendarPlugin, bake_requested_cake},
+    economy::{CakeFinishedMessage, EconomyPlugin, NewCakeMessage, Pantry, RecipeBook},
+    particles::{ParticleEmitter, ParticlePlugin},
+    recipient::Recipient,
+    replay::{Replay, live_input},
+    save::Stats,
+    trick_candles::{Relight, TrickCandlePlugin, TrickCandles},
+    tween::{Tween, TweenTarget},
+    util::despawn_all,
+    wish::WishPlugin,
 };
 
 pub struct CakePlugin;
 
 impl Plugin for CakePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Cake), (setup, spawn_amogi))
+        if !app.is_plugin_added::<EconomyPlugin>() {
+            app.add_plugins(EconomyPlugin);
+        }
+        if !app.is_plugin_added::<ParticlePlugin>() {
+            app.add_plugins(ParticlePlugin);
+        }
+        let recipient = Recipient::from_config();
+        app.add_plugins((CalendarPlugin, WishPlugin, TrickCandlePlugin));
+        app.add_plugins(RonAssetPlugin::<CakeDefinition>::new(&["cake.ron"]))
+            .insert_resource(Age(recipient.age))
+            .insert_resource(recipient)
+            .init_resource::<CandleSlots>()
+            .add_message::<BiteCakeMessage>()
+            .add_message::<ExtinguishFlameMessage>()
+            .add_systems(
+                OnEnter(GameState::Cake),
+                (setup, spawn_amogi).after(bake_requested_cake),
+            )
             .add_systems(
                 Update,
-                (animate::<FlameAnimation>, animate::<SmokeAnimation>)
+                (
+                    handle_bite_messages,
+                    handle_extinguish_messages,
+                    reload_cake_definition,
+                    bake_new_cake,
+                )
                     .run_if(in_state(GameState::Cake)),
             )
             .add_systems(OnExit(GameState::Cake), despawn_all::<OnCakeScreen>);
@@ -26,40 +60,128 @@ impl Plugin for CakePlugin {
 #[derive(Component)]
 struct OnCakeScreen;
 
-const CAKE_ATLAS_INDEX: usize = 0;
-const CAKE_ATLAS_SIZE: usize = 10;
 const PLATE_ATLAS_INDEX: usize = 10;
 const PICKLE_MEW_ATLAS_INDEX: usize = 70;
+const AMOGUS_ATLAS_INDEX: usize = 20;
+const AMOGUS_VARIANTS: usize = 5;
+const FLAME_CLIPS: [&str; 2] = ["flame", "flame_flicker"];
 
 const CAKE_SCALE: Vec3 = Vec3::splat(15.);
 const AMOGUS_SCALE: Vec3 = Vec3::splat(2.);
 const PICKLE_MEW_SCALE: Vec3 = Vec3::splat(2.);
 
+/// Candle spacing at which amogi are drawn at full [`AMOGUS_SCALE`].
+const CANDLE_SPACING: f32 = 40.;
+/// Ambient light brightness of the cake screen while the candles burn.
+pub const LIT_AMBIENT: f32 = 0.3;
+/// How far the light of a burning flame reaches.
+pub const FLAME_LIGHT_RADIUS: f32 = 120.;
+
+/// How old the birthday person is, which is also how many candles go on the cake.
+#[derive(Resource)]
+pub struct Age(pub usize);
+
+/// Describes how a cake looks while it is being eaten and which candles go with each bite.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct CakeDefinition {
+    /// Part of the cake top that candles are placed on.
+    candle_area: Rect,
+    candle_layout: CandleLayout,
+    stages: Vec<BiteStage>,
+    final_bite: FinalBite,
+    /// Candles that may light themselves again after being blown out.
+    #[serde(default)]
+    pub trick_candles: Option<TrickCandles>,
+}
+
+#[derive(Deserialize)]
+struct BiteStage {
+    frame: usize,
+    /// Horizontal span of the cake top that can still hold candles, `None` once the top is gone.
+    /// Candles standing outside of it are eaten when the cake reaches this stage.
+    candle_span: Option<(f32, f32)>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+enum CandleLayout {
+    Rows,
+    Rings,
+}
+
+/// What biting the last stage of a cake does.
+#[derive(Deserialize)]
+enum FinalBite {
+    /// Bring back the whole cake with a fresh set of candles.
+    Restart,
+    /// Leave the cake as it is.
+    Stay,
+}
+
+#[derive(Component)]
+struct Cake {
+    stage: usize,
+}
+
+#[derive(Component)]
+struct Candle {
+    slot: usize,
+}
+
+#[derive(Message)]
+pub struct BiteCakeMessage;
+
+#[derive(Message)]
+pub struct ExtinguishFlameMessage {
+    pub slot: usize,
+}
+
+/// Positions of the candle slots currently laid out on the cake.
+#[derive(Resource, Default)]
+struct CandleSlots(Vec<Vec2>);
+
 fn setup(
     mut commands: Commands,
     assets: Res<Assets>,
     mut light_query: Query<&mut Light2d, With<Camera>>,
-    mut camera_transform: Single<&mut Transform, With<Camera>>,
+    camera: Single<(Entity, &mut Transform), With<Camera>>,
     mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    definitions: Res<bevy::asset::Assets<CakeDefinition>>,
+    mut stats: ResMut<Stats>,
+    pantry: Res<Pantry>,
+    recipe_books: Res<bevy::asset::Assets<RecipeBook>>,
 ) {
+    let (camera_entity, mut camera_transform) = camera.into_inner();
+    commands.entity(camera_entity).remove::<Tween>();
     camera_transform.translation.y = 0.;
 
     commands.insert_resource(ClearColor(Color::srgb_u8(29, 29, 29)));
     for mut light in &mut light_query {
-        light.ambient_light.brightness = 0.3;
+        light.ambient_light.brightness = LIT_AMBIENT;
     }
 
     if rng.next_u32().is_multiple_of(5) {
+        stats.pickle_sightings += 1;
         commands
             .spawn((
-                Sprite::from_atlas_image(
-                    assets.textures.clone(),
-                    TextureAtlas::from(assets.texture_atlas.clone())
-                        .with_index(PICKLE_MEW_ATLAS_INDEX),
-                ),
+                Sprite {
+                    color: Color::NONE,
+                    ..Sprite::from_atlas_image(
+                        assets.textures.clone(),
+                        TextureAtlas::from(assets.texture_atlas.clone())
+                            .with_index(PICKLE_MEW_ATLAS_INDEX),
+                    )
+                },
                 Transform::from_xyz(-300., -300., 1.).with_scale(PICKLE_MEW_SCALE),
                 OnCakeScreen,
                 PickleMew,
+                Tween::new(
+                    TweenTarget::SpriteColor {
+                        start: Color::NONE,
+                        end: Color::WHITE,
+                    },
+                    1.5,
+                    EaseFunction::QuadraticIn,
+                ),
                 Pickable::default(),
             ))
             .observe(click_pickle_mew);
@@ -74,14 +196,26 @@ fn setup(
         OnCakeScreen,
     ));
 
+    let frame = definitions
+        .get(&assets.cake)
+        .and_then(|definition| definition.stages.first())
+        .map_or(0, |stage| stage.frame);
+    let tint = recipe_books
+        .get(&assets.recipes)
+        .and_then(|book| book.current(&pantry))
+        .map_or(Color::WHITE, |recipe| recipe.tint());
     commands
         .spawn((
-            Sprite::from_atlas_image(
-                assets.textures.clone(),
-                TextureAtlas::from(assets.texture_atlas.clone()).with_index(CAKE_ATLAS_INDEX),
-            ),
+            Sprite {
+                color: tint,
+                ..Sprite::from_atlas_image(
+                    assets.textures.clone(),
+                    TextureAtlas::from(assets.texture_atlas.clone()).with_index(frame),
+                )
+            },
             Transform::from_xyz(0., 0., 0.).with_scale(CAKE_SCALE),
             OnCakeScreen,
+            Cake { stage: 0 },
             Pickable::default(),
         ))
         .observe(bite_cake);
@@ -91,35 +225,59 @@ fn spawn_amogi(
     mut commands: Commands,
     assets: Res<Assets>,
     mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    definitions: Res<bevy::asset::Assets<CakeDefinition>>,
+    age: Res<Age>,
+    mut candle_slots: ResMut<CandleSlots>,
+    clips: AnimationClips,
 ) {
-    spawn_amogus::<Amogus1>(&mut commands, &mut rng, &assets, Vec2::new(95., 80.));
-    spawn_amogus::<Amogus2>(&mut commands, &mut rng, &assets, Vec2::new(-105., 75.));
-    spawn_amogus::<Amogus3>(&mut commands, &mut rng, &assets, Vec2::new(55., 60.));
-    spawn_amogus::<Amogus4>(&mut commands, &mut rng, &assets, Vec2::new(-55., 65.));
-    spawn_amogus::<Amogus5>(&mut commands, &mut rng, &assets, Vec2::new(0., 95.));
+    let Some(definition) = definitions.get(&assets.cake) else {
+        return;
+    };
+    let area = definition.candle_area;
+    let (slots, spacing) = layout_candles(definition.candle_layout, area, age.0);
+    let scale = AMOGUS_SCALE * (spacing / CANDLE_SPACING).min(1.);
+    for (slot, pos) in slots.iter().enumerate() {
+        // Candles further back on the cake are drawn behind the ones in front.
+        let depth = 2. + (area.max.y - pos.y) / area.height().max(1.);
+        spawn_amogus(
+            &mut commands,
+            &mut rng,
+            &assets,
+            &clips,
+            slot,
+            pos.extend(depth),
+            scale,
+        );
+    }
+    candle_slots.0 = slots;
 }
 
-fn spawn_amogus<A: Amogus + Component>(
+fn spawn_amogus(
     commands: &mut Commands,
     rng: &mut WyRand,
     assets: &Assets,
-    pos: Vec2,
+    clips: &AnimationClips,
+    slot: usize,
+    pos: Vec3,
+    scale: Vec3,
 ) {
-    let index = rng.next_u32() as usize % A::size();
+    let index = rng.next_u32() as usize % AMOGUS_VARIANTS;
     let flip = rng.next_u32().is_multiple_of(2);
+    let flame = clips.get(FLAME_CLIPS[rng.next_u32() as usize % FLAME_CLIPS.len()]);
     commands
         .spawn((
             Sprite {
                 image: assets.textures.clone(),
                 texture_atlas: Some(
-                    TextureAtlas::from(assets.texture_atlas.clone()).with_index(A::index() + index),
+                    TextureAtlas::from(assets.texture_atlas.clone())
+                        .with_index(AMOGUS_ATLAS_INDEX + index),
                 ),
                 flip_x: flip,
                 ..default()
             },
-            A::new(),
+            Candle { slot },
             OnCakeScreen,
-            Transform::from_translation(pos.extend(2.)).with_scale(AMOGUS_SCALE),
+            Transform::from_translation(pos).with_scale(scale),
         ))
         .with_children(|parent| {
             parent
@@ -128,20 +286,31 @@ fn spawn_amogus<A: Amogus + Component>(
                         image: assets.textures.clone(),
                         texture_atlas: Some(
                             TextureAtlas::from(assets.texture_atlas.clone())
-                                .with_index(FlameAnimation::index()),
+                                .with_index(flame.index()),
                         ),
                         flip_x: flip,
                         ..default()
                     },
                     PointLight2d {
-                        radius: 120.,
+                        radius: FLAME_LIGHT_RADIUS,
                         color: Color::srgba_u8(200, 200, 0, 128),
-                        intensity: 2.,
+                        intensity: 0.,
                         falloff: 10.,
                         ..default()
                     },
+                    Tween::new(
+                        TweenTarget::LightIntensity { start: 0., end: 3. },
+                        0.3,
+                        EaseFunction::QuadraticOut,
+                    )
+                    .then(
+                        TweenTarget::LightIntensity { start: 3., end: 2. },
+                        0.5,
+                        EaseFunction::QuadraticInOut,
+                    ),
                     Pickable::default(),
-                    FlameAnimation(Timer::from_seconds(0.1, TimerMode::Repeating)),
+                    Flame { slot },
+                    flame,
                     Transform::from_xyz(0., 0., 3.),
                 ))
                 .observe(extinguish_flame);
@@ -151,162 +320,365 @@ fn spawn_amogus<A: Amogus + Component>(
 fn bite_cake(
     event: On<Pointer<Press>>,
     mut commands: Commands,
-    mut sprites: Query<&mut Sprite>,
+    mut message_writer: MessageWriter<BiteCakeMessage>,
+    replay: Option<Res<Replay>>,
+) {
+    if live_input(replay) {
+        message_writer.write(BiteCakeMessage);
+        if let Some(position) = event.hit.position {
+            commands.spawn((
+                ParticleEmitter::crumbs(),
+                Transform::from_translation(position.with_z(5.)),
+                OnCakeScreen,
+            ));
+        }
+    }
+}
+
+fn handle_bite_messages(
+    mut commands: Commands,
+    mut bite_messages: MessageReader<BiteCakeMessage>,
+    mut cakes: Query<(&mut Cake, &mut Sprite)>,
+    candles: Query<(Entity, &Candle)>,
     assets: Res<Assets>,
+    definitions: Res<bevy::asset::Assets<CakeDefinition>>,
+    candle_slots: Res<CandleSlots>,
+    mut stats: ResMut<Stats>,
+    mut finished_messages: MessageWriter<CakeFinishedMessage>,
 ) {
-    if let Ok(mut sprite) = sprites.get_mut(event.event_target())
-        && let Some(atlas) = &mut sprite.texture_atlas
-    {
-        match atlas.index {
-            v if v == CAKE_ATLAS_INDEX => {
-                let id = commands.register_system(despawn_all::<Amogus1>);
-                commands.run_system(id);
-            }
-            v if v == CAKE_ATLAS_INDEX + 1 => {
-                let id = commands.register_system(despawn_all::<Amogus2>);
-                commands.run_system(id);
-            }
-            v if v == CAKE_ATLAS_INDEX + 3 => {
-                let id = commands.register_system(despawn_all::<Amogus4>);
-                commands.run_system(id);
+    let Some(definition) = definitions.get(&assets.cake) else {
+        return;
+    };
+    if definition.stages.is_empty() {
+        return;
+    }
 
-                let id = commands.register_system(despawn_all::<Amogus5>);
-                commands.run_system(id);
-            }
-            v if v == CAKE_ATLAS_INDEX + 4 => {
-                let id = commands.register_system(despawn_all::<Amogus3>);
-                commands.run_system(id);
+    for _ in bite_messages.read() {
+        for (mut cake, mut sprite) in &mut cakes {
+            if cake.stage + 1 >= definition.stages.len() {
+                match definition.final_bite {
+                    FinalBite::Restart => {
+                        commands.run_system_cached(spawn_amogi);
+                        cake.stage = 0;
+                    }
+                    FinalBite::Stay => continue,
+                }
+            } else {
+                cake.stage += 1;
+                let span = definition.stages[cake.stage].candle_span;
+                for (entity, candle) in &candles {
+                    let eaten = match (span, candle_slots.0.get(candle.slot)) {
+                        (Some((min, max)), Some(pos)) => pos.x < min || pos.x > max,
+                        _ => true,
+                    };
+                    if eaten {
+                        commands.entity(entity).despawn();
+                    }
+                }
+                commands.spawn(AudioPlayer(assets.eating_sound.clone()));
+                if cake.stage + 1 == definition.stages.len() {
+                    stats.cakes_eaten += 1;
+                    finished_messages.write(CakeFinishedMessage);
+                    commands.spawn((
+                        ParticleEmitter::confetti(),
+                        Transform::from_xyz(0., 200., 10.),
+                        OnCakeScreen,
+                    ));
+                }
             }
-            v if v == CAKE_ATLAS_INDEX + CAKE_ATLAS_SIZE - 1 => {
-                let id = commands.register_system(spawn_amogi);
-                commands.run_system(id);
+
+            if let Some(atlas) = &mut sprite.texture_atlas {
+                atlas.index = definition.stages[cake.stage].frame;
             }
-            _ => (),
         }
-        if atlas.index != CAKE_ATLAS_INDEX + CAKE_ATLAS_SIZE - 1 {
-            commands.spawn(AudioPlayer(assets.eating_sound.clone()));
-        }
-
-        atlas.index = (atlas.index + 1 - CAKE_ATLAS_INDEX) % CAKE_ATLAS_SIZE + CAKE_ATLAS_INDEX;
     }
 }
 
-fn extinguish_flame(
-    event: On<Pointer<Press>>,
+fn reload_cake_definition(
     mut commands: Commands,
-    mut query: Query<(&mut Sprite, &mut PointLight2d)>,
+    mut asset_events: MessageReader<AssetEvent<CakeDefinition>>,
+    assets: Res<Assets>,
+    definitions: Res<bevy::asset::Assets<CakeDefinition>>,
+    mut cakes: Query<(&mut Cake, &mut Sprite)>,
+    candles: Query<Entity, With<Candle>>,
 ) {
-    if let Ok((mut sprite, mut light)) = query.get_mut(event.event_target())
-        && let Some(atlas) = &mut sprite.texture_atlas
+    if !asset_events
+        .read()
+        .any(|event| event.is_modified(&assets.cake))
     {
-        light.radius = 0.;
-        commands
-            .entity(event.event_target())
-            .remove::<Pickable>()
-            .remove::<FlameAnimation>()
-            .insert(SmokeAnimation(Timer::from_seconds(
-                0.3,
-                TimerMode::Repeating,
-            )));
-        atlas.index = SmokeAnimation::index();
+        return;
+    }
+    let Some(stage) = definitions
+        .get(&assets.cake)
+        .and_then(|definition| definition.stages.first())
+    else {
+        return;
+    };
+
+    for (mut cake, mut sprite) in &mut cakes {
+        cake.stage = 0;
+        if let Some(atlas) = &mut sprite.texture_atlas {
+            atlas.index = stage.frame;
+        }
+    }
+    for entity in &candles {
+        commands.entity(entity).despawn();
     }
+    commands.run_system_cached(spawn_amogi);
 }
 
-#[derive(Component)]
-struct Amogus1;
-#[derive(Component)]
-struct Amogus2;
-#[derive(Component)]
-struct Amogus3;
-#[derive(Component)]
-struct Amogus4;
-#[derive(Component)]
-struct Amogus5;
-
-#[derive(Component)]
-struct PickleMew;
-
-trait Amogus {
-    fn index() -> usize {
-        20
+/// Swaps the cake for a whole one of the recipe the bakery switched to.
+fn bake_new_cake(
+    mut commands: Commands,
+    mut new_cake_messages: MessageReader<NewCakeMessage>,
+    assets: Res<Assets>,
+    definitions: Res<bevy::asset::Assets<CakeDefinition>>,
+    pantry: Res<Pantry>,
+    recipe_books: Res<bevy::asset::Assets<RecipeBook>>,
+    mut cakes: Query<(&mut Cake, &mut Sprite)>,
+    candles: Query<Entity, With<Candle>>,
+) {
+    if new_cake_messages.read().count() == 0 {
+        return;
     }
-    fn size() -> usize {
-        5
+    let (Some(stage), Some(recipe)) = (
+        definitions
+            .get(&assets.cake)
+            .and_then(|definition| definition.stages.first()),
+        recipe_books
+            .get(&assets.recipes)
+            .and_then(|book| book.current(&pantry)),
+    ) else {
+        return;
+    };
+
+    for (mut cake, mut sprite) in &mut cakes {
+        cake.stage = 0;
+        sprite.color = recipe.tint();
+        if let Some(atlas) = &mut sprite.texture_atlas {
+            atlas.index = stage.frame;
+        }
+    }
+    for entity in &candles {
+        commands.entity(entity).despawn();
     }
-    fn new() -> Self;
+    commands.run_system_cached(spawn_amogi);
 }
 
-impl Amogus for Amogus1 {
-    fn new() -> Self {
-        Self
-    }
+/// Lays out `count` candle slots inside `area` and returns them together with the distance
+/// between the two closest candles.
+fn layout_candles(layout: CandleLayout, area: Rect, count: usize) -> (Vec<Vec2>, f32) {
+    // Try the row/ring counts and keep the one that leaves the most room between candles. The
+    // room peaks around the square root of the count, stretched for areas taller than wide, so
+    // more lines than that never win.
+    let max_lines = (count as f32 * (area.height() / area.width()).max(1.))
+        .sqrt()
+        .ceil() as usize
+        + 1;
+    (1..=max_lines.min(count).max(1))
+        .map(|lines| {
+            let slots = match layout {
+                CandleLayout::Rows => layout_rows(area, count, lines),
+                CandleLayout::Rings => layout_rings(area, count, lines),
+            };
+            let spacing = min_distance(&slots).unwrap_or(area.width());
+            (slots, spacing)
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap_or_default()
 }
 
-impl Amogus for Amogus2 {
-    fn new() -> Self {
-        Self
-    }
+fn layout_rows(area: Rect, count: usize, rows: usize) -> Vec<Vec2> {
+    let per_row = count.div_ceil(rows);
+    (0..count)
+        .map(|i| {
+            let row = i / per_row;
+            let in_row = per_row.min(count - row * per_row);
+            let x = area.min.x + ((i % per_row) as f32 + 0.5) * area.width() / in_row as f32;
+            let y = area.max.y - (row as f32 + 0.5) * area.height() / rows as f32;
+            Vec2::new(x, y)
+        })
+        .collect()
 }
 
-impl Amogus for Amogus3 {
-    fn new() -> Self {
-        Self
+fn layout_rings(area: Rect, count: usize, rings: usize) -> Vec<Vec2> {
+    // Outer rings are longer, so they get proportionally more candles.
+    let total_weight = rings * (rings + 1) / 2;
+    let mut slots = Vec::with_capacity(count);
+    for ring in 1..=rings {
+        let in_ring = if ring == rings {
+            count - slots.len()
+        } else {
+            (count * ring / total_weight).min(count - slots.len())
+        };
+        let radius = area.half_size() * ring as f32 / rings as f32;
+        let offset = if ring % 2 == 0 { 0.5 } else { 0. };
+        for i in 0..in_ring {
+            let angle = std::f32::consts::FRAC_PI_2
+                + std::f32::consts::TAU * (i as f32 + offset) / in_ring as f32;
+            slots.push(area.center() + radius * Vec2::new(angle.cos(), angle.sin()));
+        }
     }
+    slots
 }
 
-impl Amogus for Amogus4 {
-    fn new() -> Self {
-        Self
+fn min_distance(slots: &[Vec2]) -> Option<f32> {
+    slots
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| slots[i + 1..].iter().map(move |b| a.distance(*b)))
+        .min_by(f32::total_cmp)
+}
+
+fn extinguish_flame(
+    event: On<Pointer<Press>>,
+    mut message_writer: MessageWriter<ExtinguishFlameMessage>,
+    flames: Query<&Flame>,
+    replay: Option<Res<Replay>>,
+) {
+    if let Ok(flame) = flames.get(event.event_target())
+        && live_input(replay)
+    {
+        message_writer.write(ExtinguishFlameMessage { slot: flame.slot });
     }
 }
 
-impl Amogus for Amogus5 {
-    fn new() -> Self {
-        Self
+pub fn handle_extinguish_messages(
+    mut commands: Commands,
+    mut extinguish_messages: MessageReader<ExtinguishFlameMessage>,
+    mut flames: Query<(
+        Entity,
+        &Flame,
+        &mut Sprite,
+        &mut PointLight2d,
+        &GlobalTransform,
+    )>,
+    clips: AnimationClips,
+    mut stats: ResMut<Stats>,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    assets: Res<Assets>,
+    definitions: Res<bevy::asset::Assets<CakeDefinition>>,
+) {
+    let trick_candles = definitions
+        .get(&assets.cake)
+        .and_then(|definition| definition.trick_candles.as_ref());
+    // The flame is only gone once the commands are applied, so a slot can come up twice.
+    let mut extinguished = Vec::new();
+    for msg in extinguish_messages.read() {
+        if extinguished.contains(&msg.slot) {
+            continue;
+        }
+        extinguished.push(msg.slot);
+        for (entity, flame, mut sprite, mut light, transform) in &mut flames {
+            if flame.slot != msg.slot {
+                continue;
+            }
+            stats.flames_extinguished += 1;
+            light.radius = 0.;
+            let relight = trick_candles.and_then(|trick_candles| trick_candles.roll(&mut rng));
+            // Trick candles smoke just like the rest, so there's no telling which will relight.
+            let smoke = clips.get("smoke_puff");
+            if let Some(atlas) = &mut sprite.texture_atlas {
+                atlas.index = smoke.index();
+            }
+            let mut entity = commands.entity(entity);
+            entity.remove::<(Pickable, Flame)>().insert(smoke);
+            match relight {
+                Some(delay) => entity.insert(Relight::new(flame.slot, delay)),
+                None => entity.insert(DespawnOnFinish),
+            };
+            commands.spawn((
+                ParticleEmitter::smoke(),
+                Transform::from_translation(transform.translation() + Vec3::Z),
+                OnCakeScreen,
+            ));
+        }
     }
 }
 
 #[derive(Component)]
-struct FlameAnimation(Timer);
+struct PickleMew;
+
 #[derive(Component)]
-struct SmokeAnimation(Timer);
+pub struct Flame {
+    pub slot: usize,
+}
 
-impl Animation for FlameAnimation {
-    fn index() -> usize {
-        30
+fn click_pickle_mew(
+    event: On<Pointer<Press>>,
+    mut commands: Commands,
+    assets: Res<Assets>,
+    mut query: Query<Entity, With<PickleMew>>,
+) {
+    if let Ok(entity) = query.get_mut(event.event_target()) {
+        commands.spawn(AudioPlayer(assets.pickle_mew.clone()));
+        commands.entity(entity).despawn();
     }
+}
 
-    fn size() -> usize {
-        5
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect {
+        min: Vec2::new(-100., -25.),
+        max: Vec2::new(100., 25.),
+    };
+
+    fn check_layout(layout: CandleLayout, count: usize) {
+        let (slots, spacing) = layout_candles(layout, AREA, count);
+        assert_eq!(slots.len(), count);
+        for slot in &slots {
+            assert!(
+                AREA.inflate(0.01).contains(*slot),
+                "{slot} outside the area"
+            );
+        }
+        match min_distance(&slots) {
+            Some(distance) => assert_eq!(distance, spacing),
+            None => assert_eq!(spacing, AREA.width()),
+        }
+        assert!(spacing > 0., "candles on top of each other");
     }
 
-    fn timer(&mut self) -> &mut Timer {
-        &mut self.0
+    #[test]
+    fn rows_fit_every_candle() {
+        for count in [1, 5, 30] {
+            check_layout(CandleLayout::Rows, count);
+        }
     }
-}
 
-impl Animation for SmokeAnimation {
-    fn index() -> usize {
-        40
+    #[test]
+    fn rings_fit_every_candle() {
+        for count in [1, 5, 30] {
+            check_layout(CandleLayout::Rings, count);
+        }
     }
 
-    fn size() -> usize {
-        5
+    #[test]
+    fn rows_split_candles_evenly() {
+        let slots = layout_rows(AREA, 5, 2);
+        assert_eq!(slots.len(), 5);
+        // Three candles on the top row, two below.
+        assert_eq!(slots.iter().filter(|slot| slot.y > 0.).count(), 3);
+        assert_eq!(slots[0].y, slots[2].y);
+        assert_eq!(slots[3].y, slots[4].y);
     }
 
-    fn timer(&mut self) -> &mut Timer {
-        &mut self.0
+    #[test]
+    fn more_candles_are_closer_together() {
+        for layout in [CandleLayout::Rows, CandleLayout::Rings] {
+            let (_, five) = layout_candles(layout, AREA, 5);
+            let (_, thirty) = layout_candles(layout, AREA, 30);
+            assert!(thirty < five);
+        }
     }
-}
 
-fn click_pickle_mew(
-    event: On<Pointer<Press>>,
-    mut commands: Commands,
-    assets: Res<Assets>,
-    mut query: Query<Entity, With<PickleMew>>,
-) {
-    if let Ok(entity) = query.get_mut(event.event_target()) {
-        commands.spawn(AudioPlayer(assets.pickle_mew.clone()));
-        commands.entity(entity).despawn();
+    #[test]
+    fn min_distance_finds_the_closest_pair() {
+        assert_eq!(min_distance(&[]), None);
+        assert_eq!(min_distance(&[Vec2::ZERO]), None);
+        let slots = [Vec2::ZERO, Vec2::new(10., 0.), Vec2::new(10., 3.)];
+        assert_eq!(min_distance(&slots), Some(3.));
     }
 }
@@ -0,0 +1,390 @@
+use bevy::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState, args,
+    assets::Assets,
+    cake::Age,
+    economy::{CakeFinishedMessage, NewCakeMessage},
+    recipient::{MAX_AGE, Recipient},
+    replay::live_input,
+    util::{despawn_all, unix_time},
+};
+
+/// Greets whoever in the team calendar has a birthday today, or counts down to the next one.
+///
+/// A recipient named in the launch options takes precedence over the calendar, and so does a cake
+/// named with `--cake`.
+pub struct CalendarPlugin;
+
+impl Plugin for CalendarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<Calendar>::new(&["calendar.ron"]))
+            .insert_resource(Today::now())
+            .init_resource::<Celebration>()
+            .add_systems(
+                OnEnter(GameState::Cake),
+                (
+                    celebrate_today.run_if(|| args::get("name").is_none()),
+                    bake_requested_cake
+                        .after(celebrate_today)
+                        .run_if(|| args::get("cake").is_some()),
+                    setup_cake,
+                ),
+            )
+            .add_systems(
+                Update,
+                next_birthday_person.run_if(in_state(GameState::Cake)),
+            )
+            .add_systems(OnExit(GameState::Cake), despawn_all::<OnCakeScreen>)
+            .add_systems(OnEnter(GameState::Calendar), setup_calendar)
+            .add_systems(OnExit(GameState::Calendar), despawn_all::<OnCalendarScreen>)
+            .add_systems(Update, handle_calendar_buttons.run_if(live_input));
+    }
+}
+
+#[derive(Component)]
+struct OnCakeScreen;
+
+#[derive(Component)]
+struct OnCalendarScreen;
+
+/// Cake baked for birthdays that don't name one.
+const DEFAULT_CAKE: &str = "cakes/birthday.cake.ron";
+/// How many birthdays the calendar screen lists.
+const UPCOMING_SHOWN: usize = 10;
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Everyone whose birthday the game knows about.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct Calendar {
+    birthdays: Vec<Birthday>,
+}
+
+#[derive(Deserialize)]
+struct Birthday {
+    name: String,
+    month: u32,
+    day: u32,
+    /// Year of birth, for the number of candles.
+    born: i64,
+    /// Path of one of the bundled cake definitions, [`DEFAULT_CAKE`] if not given.
+    #[serde(default)]
+    cake: Option<String>,
+}
+
+/// Today's date as days since the Unix epoch, in UTC. Recorded so replays celebrate the same day.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct Today(u64);
+
+impl Today {
+    fn now() -> Self {
+        Self(unix_time() / (24 * 60 * 60))
+    }
+
+    /// Year, month and day of the date `days` after today.
+    fn date_in(self, days: u64) -> (i64, u32, u32) {
+        // Howard Hinnant's `civil_from_days`, with years starting in March.
+        let z = (self.0 + days) as i64 + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        (year, month, day)
+    }
+
+    /// Days until the next `birthday`, 0 if it is today. Leap day birthdays may be years away,
+    /// and so may the first birthday of someone born in the future.
+    fn days_until(self, birthday: &Birthday) -> Option<u64> {
+        (0..4 * 366).find(|&days| {
+            let (year, month, day) = self.date_in(days);
+            (month, day) == (birthday.month, birthday.day) && year > birthday.born
+        })
+    }
+}
+
+/// Who the cake screen is celebrating.
+#[derive(Resource, Default)]
+pub enum Celebration {
+    /// The recipient from the launch options or the bundled config.
+    #[default]
+    Recipient,
+    /// Calendar entries with a birthday today, taking turns with every cake eaten up.
+    Birthdays { people: Vec<usize>, turn: usize },
+    /// Nobody has a birthday today.
+    Countdown { name: String, days: u64 },
+}
+
+impl Celebration {
+    /// Text for the cake screen banner.
+    pub fn banner(&self, recipient: &Recipient) -> String {
+        match self {
+            Celebration::Countdown { name, days: 1 } => format!("{name}'s birthday is tomorrow!"),
+            Celebration::Countdown { name, days } => {
+                format!("{days} days until {name}'s birthday")
+            }
+            _ => recipient.banner(),
+        }
+    }
+}
+
+#[derive(Component)]
+enum CalendarButton {
+    Open,
+    Back,
+}
+
+/// Works out who has a birthday today and sets up their cake, keeping whose turn it is.
+pub fn celebrate_today(
+    mut celebration: ResMut<Celebration>,
+    mut recipient: ResMut<Recipient>,
+    mut age: ResMut<Age>,
+    mut assets: ResMut<Assets>,
+    asset_server: Res<AssetServer>,
+    calendars: Res<bevy::asset::Assets<Calendar>>,
+    today: Res<Today>,
+) {
+    let Some(calendar) = calendars.get(&assets.calendar) else {
+        return;
+    };
+    let people: Vec<usize> = (0..calendar.birthdays.len())
+        .filter(|&i| today.days_until(&calendar.birthdays[i]) == Some(0))
+        .collect();
+    if people.is_empty() {
+        *celebration = calendar
+            .birthdays
+            .iter()
+            .filter_map(|birthday| Some((today.days_until(birthday)?, birthday)))
+            .min_by_key(|(days, _)| *days)
+            .map_or(Celebration::Recipient, |(days, birthday)| {
+                Celebration::Countdown {
+                    name: birthday.name.clone(),
+                    days,
+                }
+            });
+        return;
+    }
+
+    let turn = match *celebration {
+        Celebration::Birthdays { turn, .. } => turn % people.len(),
+        _ => 0,
+    };
+    greet(
+        &calendar.birthdays[people[turn]],
+        *today,
+        &mut recipient,
+        &mut age,
+        &mut assets,
+        &asset_server,
+    );
+    *celebration = Celebration::Birthdays { people, turn };
+}
+
+/// Passes the cake on to the next person sharing today's birthday once one is eaten up.
+fn next_birthday_person(
+    mut finished_messages: MessageReader<CakeFinishedMessage>,
+    mut new_cake_messages: MessageWriter<NewCakeMessage>,
+    mut celebration: ResMut<Celebration>,
+    mut recipient: ResMut<Recipient>,
+    mut age: ResMut<Age>,
+    mut assets: ResMut<Assets>,
+    asset_server: Res<AssetServer>,
+    calendars: Res<bevy::asset::Assets<Calendar>>,
+    today: Res<Today>,
+) {
+    let finished = finished_messages.read().count();
+    if finished == 0 {
+        return;
+    }
+    let Celebration::Birthdays { people, turn } = &mut *celebration else {
+        return;
+    };
+    if people.len() < 2 {
+        return;
+    }
+    let Some(calendar) = calendars.get(&assets.calendar) else {
+        return;
+    };
+    *turn = (*turn + finished) % people.len();
+    greet(
+        &calendar.birthdays[people[*turn]],
+        *today,
+        &mut recipient,
+        &mut age,
+        &mut assets,
+        &asset_server,
+    );
+    new_cake_messages.write(NewCakeMessage);
+}
+
+fn greet(
+    birthday: &Birthday,
+    today: Today,
+    recipient: &mut Recipient,
+    age: &mut Age,
+    assets: &mut Assets,
+    asset_server: &AssetServer,
+) {
+    let (year, _, _) = today.date_in(0);
+    recipient.name = birthday.name.clone();
+    recipient.age = ((year - birthday.born).max(0) as usize).min(MAX_AGE);
+    age.0 = recipient.age;
+    // A cake named in the launch options is baked for everyone.
+    if args::get("cake").is_some() {
+        return;
+    }
+    let path = birthday.cake.as_deref().unwrap_or(DEFAULT_CAKE);
+    match asset_server.get_handle(path.to_owned()) {
+        Some(cake) => assets.cake = cake,
+        None => warn!("{} wants {path}, which isn't a bundled cake", birthday.name),
+    }
+}
+
+/// Swaps in the bundled cake named with the `--cake` launch option.
+pub fn bake_requested_cake(mut assets: ResMut<Assets>, asset_server: Res<AssetServer>) {
+    let Some(path) = args::get("cake") else {
+        return;
+    };
+    match asset_server.get_handle(path.clone()) {
+        Some(cake) => assets.cake = cake,
+        None => warn!("--cake asks for {path}, which isn't a bundled cake"),
+    }
+}
+
+fn setup_cake(mut commands: Commands) {
+    commands.spawn((
+        Button,
+        CalendarButton::Open,
+        Node {
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            padding: UiRect::all(Val::Px(8.)),
+            position_type: PositionType::Absolute,
+            top: Val::Px(0.),
+            right: Val::Px(0.),
+            ..default()
+        },
+        BackgroundColor(Color::srgb_u8(200, 200, 200)),
+        OnCakeScreen,
+        children![(
+            Text::new("birthdays"),
+            TextFont {
+                font_size: 20.,
+                ..default()
+            },
+            TextColor(Color::srgb_u8(29, 29, 29)),
+        )],
+    ));
+}
+
+fn setup_calendar(
+    mut commands: Commands,
+    assets: Res<Assets>,
+    calendars: Res<bevy::asset::Assets<Calendar>>,
+    today: Res<Today>,
+) {
+    let mut upcoming: Vec<(u64, &Birthday)> = calendars
+        .get(&assets.calendar)
+        .map(|calendar| {
+            calendar
+                .birthdays
+                .iter()
+                .filter_map(|birthday| Some((today.days_until(birthday)?, birthday)))
+                .collect()
+        })
+        .unwrap_or_default();
+    upcoming.sort_by_key(|(days, _)| *days);
+
+    commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(8.),
+                width: Val::Percent(100.),
+                padding: UiRect::top(Val::Percent(5.)),
+                ..default()
+            },
+            OnCalendarScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("upcoming birthdays"),
+                TextFont {
+                    font_size: 48.,
+                    ..default()
+                },
+                TextColor(Color::srgb_u8(243, 207, 198)),
+            ));
+            if upcoming.is_empty() {
+                parent.spawn(Text::new("the calendar is empty"));
+            }
+            for (days, birthday) in upcoming.into_iter().take(UPCOMING_SHOWN) {
+                let (year, month, day) = today.date_in(days);
+                let when = match days {
+                    0 => "today!".to_string(),
+                    1 => "tomorrow".to_string(),
+                    _ => format!("in {days} days"),
+                };
+                parent.spawn((
+                    Text::new(format!(
+                        "{} turns {} on {} {day}, {when}",
+                        birthday.name,
+                        year - birthday.born,
+                        MONTHS[month as usize - 1],
+                    )),
+                    TextFont {
+                        font_size: 24.,
+                        ..default()
+                    },
+                ));
+            }
+        });
+
+    commands.spawn((
+        Button,
+        CalendarButton::Back,
+        Node {
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            width: Val::Px(240.),
+            height: Val::Px(60.),
+            position_type: PositionType::Absolute,
+            bottom: Val::Percent(0.),
+            right: Val::Percent(0.),
+            ..default()
+        },
+        BackgroundColor(Color::srgb_u8(200, 200, 200)),
+        OnCalendarScreen,
+        children![(
+            Text::new("Want more cake?"),
+            TextFont {
+                font_size: 24.,
+                ..default()
+            },
+            TextColor(Color::srgb_u8(29, 29, 29)),
+        )],
+    ));
+}
+
+fn handle_calendar_buttons(
+    interaction_query: Query<(&Interaction, &CalendarButton), Changed<Interaction>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        game_state.set(match button {
+            CalendarButton::Open => GameState::Calendar,
+            CalendarButton::Back => GameState::Cake,
+        });
+    }
+}
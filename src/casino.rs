@@ -0,0 +1,621 @@
+use bevy::prelude::*;
+use bevy_light_2d::prelude::*;
+use bevy_rand::prelude::*;
+use rand::seq::IteratorRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState,
+    assets::Assets,
+    credit::{Credit, CreditTerms},
+    economy::Score,
+    gamba::{Bet, BetChange, BetChangeMessage, BetResultMessage, SettledRound, go_broke},
+    replay::live_input,
+    rules::Settlement,
+    safeguards::{SafeguardSettings, Session},
+    save::Stats,
+    tween::Tween,
+    util::despawn_all,
+};
+
+mod crash;
+mod dice;
+mod race;
+mod roulette;
+mod slots;
+
+/// The casino hub and the games that can be played from it.
+pub struct CasinoPlugin;
+
+impl Plugin for CasinoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CasinoGames>()
+            .add_sub_state::<CasinoState>()
+            .add_message::<CasinoBetMessage>()
+            .add_message::<CasinoPayoutMessage>()
+            .add_plugins((
+                dice::DicePlugin,
+                slots::SlotsPlugin,
+                roulette::RoulettePlugin,
+                crash::CrashPlugin,
+                race::RacePlugin,
+            ))
+            .add_systems(OnEnter(GameState::Casino), setup)
+            .add_systems(
+                Update,
+                (
+                    build_page.run_if(state_changed::<CasinoState>),
+                    handle_casino_buttons.run_if(live_input),
+                    start_round.run_if(round_open),
+                    settle_round,
+                    update_casino_displays,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Casino)),
+            )
+            .add_systems(OnEnter(CasinoState::Hub), abandon_round)
+            .add_systems(
+                OnExit(GameState::Casino),
+                (abandon_round, despawn_all::<OnCasinoScreen>),
+            );
+    }
+}
+
+#[derive(Component)]
+struct OnCasinoScreen;
+
+/// Everything that is rebuilt when switching between the hub and a game.
+#[derive(Component)]
+struct OnCasinoPage;
+
+/// A game that can be played from the casino hub with the shared bet controls.
+///
+/// The game's plugin registers it with [`CasinoAppExt::add_casino_game`] and starts a round
+/// whenever a [`CasinoRound`] is added while [`playing`] it. Once the round is over it sends a
+/// [`CasinoPayoutMessage`] and the casino settles the [`Score`]. The stake is taken when the
+/// round starts and the player can't leave the game until it is over.
+pub trait CasinoGame: 'static {
+    /// Unique name used in [`CasinoState::Game`].
+    const ID: &'static str;
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+    /// Labels of the bets offered below the bet controls, indexed by [`CasinoRound::choice`].
+    const CHOICES: &'static [&'static str];
+    /// How many choices there are. Choices past [`CasinoGame::CHOICES`] have no button, the game
+    /// offers them with its own UI and sends the [`CasinoBetMessage`] itself.
+    const CHOICE_COUNT: usize = Self::CHOICES.len();
+}
+
+pub trait CasinoAppExt {
+    /// Lists `G` in the casino hub.
+    fn add_casino_game<G: CasinoGame>(&mut self) -> &mut Self;
+}
+
+impl CasinoAppExt for App {
+    fn add_casino_game<G: CasinoGame>(&mut self) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<CasinoGames>()
+            .0
+            .push(CasinoGameEntry {
+                id: G::ID,
+                name: G::NAME,
+                description: G::DESCRIPTION,
+                choices: G::CHOICES,
+                choice_count: G::CHOICE_COUNT,
+            });
+        self
+    }
+}
+
+struct CasinoGameEntry {
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    choices: &'static [&'static str],
+    choice_count: usize,
+}
+
+/// Games listed in the hub, in the order they were registered.
+#[derive(Resource, Default)]
+struct CasinoGames(Vec<CasinoGameEntry>);
+
+impl CasinoGames {
+    fn get(&self, id: &str) -> Option<&CasinoGameEntry> {
+        self.0.iter().find(|game| game.id == id)
+    }
+}
+
+#[derive(SubStates, Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+#[source(GameState = GameState::Casino)]
+pub enum CasinoState {
+    #[default]
+    Hub,
+    /// Playing the game with this [`CasinoGame::ID`].
+    Game(String),
+}
+
+/// Run condition that is true while `G` is being played.
+pub fn playing<G: CasinoGame>(casino_state: Option<Res<State<CasinoState>>>) -> bool {
+    casino_state.is_some_and(|state| matches!(state.get(), CasinoState::Game(id) if id == G::ID))
+}
+
+/// Run condition that is true while no round is in progress.
+pub fn round_open(round: Option<Res<CasinoRound>>) -> bool {
+    round.is_none()
+}
+
+/// Bets on one of the current game's [`CasinoGame::CHOICES`].
+#[derive(Message, Clone, Serialize, Deserialize)]
+pub struct CasinoBetMessage {
+    pub choice: usize,
+}
+
+/// The round in progress, present from the bet until its payout. Its stake has already been
+/// taken from the [`Score`].
+#[derive(Resource)]
+pub struct CasinoRound {
+    pub stake: u64,
+    pub choice: usize,
+}
+
+/// Sent by a game once its round is decided.
+#[derive(Message)]
+pub struct CasinoPayoutMessage {
+    pub stake: u64,
+    /// Everything paid back, including the stake. 0 when the round was lost.
+    pub payout: u64,
+    /// What happened, e.g. "rolled 4 + 3".
+    pub outcome: String,
+}
+
+#[derive(Component)]
+enum CasinoButton {
+    Open(&'static str),
+    SugarCaneGamba,
+    Hub,
+    Cake,
+    IncreaseBet,
+    DecreaseBet,
+    Choice(usize),
+}
+
+#[derive(Component)]
+struct ScoreDisplay;
+
+#[derive(Component)]
+struct BetDisplay;
+
+#[derive(Component)]
+struct StatusDisplay;
+
+fn setup(
+    mut commands: Commands,
+    mut light_query: Query<&mut Light2d, With<Camera>>,
+    camera: Single<(Entity, &mut Transform), With<Camera>>,
+) {
+    let (camera_entity, mut camera_transform) = camera.into_inner();
+    commands.entity(camera_entity).remove::<Tween>();
+    camera_transform.translation.y = 0.;
+
+    commands.insert_resource(ClearColor(Color::srgb_u8(48, 16, 64)));
+    for mut light in &mut light_query {
+        light.ambient_light.brightness = 1.;
+    }
+
+    commands.spawn((
+        Node {
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            width: Val::Percent(100.),
+            height: Val::Percent(15.),
+            ..default()
+        },
+        OnCasinoScreen,
+        children![(
+            Text::new("$"),
+            TextFont {
+                font_size: 24.,
+                ..default()
+            },
+            children![(TextSpan::default(), ScoreDisplay)],
+        )],
+    ));
+}
+
+/// Spawns the game list in the hub, or the shared bet controls of the game being played.
+fn build_page(
+    mut commands: Commands,
+    casino_state: Res<State<CasinoState>>,
+    games: Res<CasinoGames>,
+    pages: Query<Entity, With<OnCasinoPage>>,
+) {
+    for page in &pages {
+        commands.entity(page).despawn();
+    }
+
+    let CasinoState::Game(id) = casino_state.get() else {
+        commands
+            .spawn((
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(12.),
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                OnCasinoPage,
+                OnCasinoScreen,
+                children![(
+                    Text::new("casino"),
+                    TextFont {
+                        font_size: 48.,
+                        ..default()
+                    },
+                )],
+            ))
+            .with_children(|parent| {
+                for game in &games.0 {
+                    parent.spawn(casino_button(
+                        CasinoButton::Open(game.id),
+                        format!("{}\n{}", game.name, game.description),
+                    ));
+                }
+                parent.spawn(casino_button(
+                    CasinoButton::SugarCaneGamba,
+                    "sugar cane gamba\nbet on which cane grows next".into(),
+                ));
+            });
+        commands.spawn((
+            back_button(CasinoButton::Cake, "Want more cake?"),
+            OnCasinoPage,
+        ));
+        return;
+    };
+    let Some(game) = games.get(id) else {
+        return;
+    };
+
+    commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(8.),
+                width: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                bottom: Val::Percent(5.),
+                ..default()
+            },
+            OnCasinoPage,
+            OnCasinoScreen,
+            children![
+                (
+                    Text::default(),
+                    TextFont {
+                        font_size: 20.,
+                        ..default()
+                    },
+                    StatusDisplay,
+                ),
+                (
+                    Node {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(12.),
+                        ..default()
+                    },
+                    children![
+                        bet_button(CasinoButton::DecreaseBet, "<", Color::srgb_u8(128, 0, 0)),
+                        (
+                            Text::new("bet: $"),
+                            TextFont {
+                                font_size: 24.,
+                                ..default()
+                            },
+                            children![(TextSpan::default(), BetDisplay)],
+                        ),
+                        bet_button(CasinoButton::IncreaseBet, ">", Color::srgb_u8(0, 128, 0)),
+                    ],
+                ),
+            ],
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(Node {
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::Center,
+                    column_gap: Val::Px(8.),
+                    row_gap: Val::Px(8.),
+                    max_width: Val::Percent(80.),
+                    ..default()
+                })
+                .with_children(|row| {
+                    for (choice, label) in game.choices.iter().enumerate() {
+                        row.spawn(casino_button(CasinoButton::Choice(choice), (*label).into()));
+                    }
+                });
+        });
+    commands.spawn((
+        back_button(CasinoButton::Hub, "back to the casino"),
+        OnCasinoPage,
+    ));
+}
+
+fn casino_button(action: CasinoButton, label: String) -> impl Bundle {
+    (
+        Button,
+        action,
+        Node {
+            padding: UiRect::all(Val::Px(8.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgb_u8(69, 69, 69)),
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size: 18.,
+                ..default()
+            },
+            TextLayout::new_with_justify(Justify::Center),
+        )],
+    )
+}
+
+fn bet_button(action: CasinoButton, label: &str, color: Color) -> impl Bundle + use<> {
+    (
+        Button,
+        action,
+        Node {
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            width: Val::Px(48.),
+            height: Val::Px(48.),
+            ..default()
+        },
+        BackgroundColor(color),
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size: 24.,
+                ..default()
+            },
+        )],
+    )
+}
+
+fn back_button(action: CasinoButton, label: &str) -> impl Bundle + use<> {
+    (
+        Button,
+        action,
+        Node {
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            width: Val::Px(240.),
+            height: Val::Px(60.),
+            position_type: PositionType::Absolute,
+            bottom: Val::Percent(0.),
+            right: Val::Percent(0.),
+            ..default()
+        },
+        BackgroundColor(Color::srgb_u8(200, 200, 200)),
+        OnCasinoScreen,
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size: 24.,
+                ..default()
+            },
+            TextColor(Color::srgb_u8(29, 29, 29)),
+        )],
+    )
+}
+
+fn handle_casino_buttons(
+    interaction_query: Query<(&Interaction, &CasinoButton), Changed<Interaction>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut casino_state: ResMut<NextState<CasinoState>>,
+    mut bet_change_messages: MessageWriter<BetChangeMessage>,
+    mut bet_messages: MessageWriter<CasinoBetMessage>,
+    round: Option<Res<CasinoRound>>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            // The stake is on the table, so nobody leaves before the round is over.
+            CasinoButton::Open(_)
+            | CasinoButton::SugarCaneGamba
+            | CasinoButton::Hub
+            | CasinoButton::Cake
+                if round.is_some() => {}
+            CasinoButton::Open(id) => casino_state.set(CasinoState::Game((*id).into())),
+            CasinoButton::SugarCaneGamba => game_state.set(GameState::Gamba),
+            CasinoButton::Hub => casino_state.set(CasinoState::Hub),
+            CasinoButton::Cake => game_state.set(GameState::Cake),
+            CasinoButton::IncreaseBet => {
+                bet_change_messages.write(BetChangeMessage(BetChange::Increase));
+            }
+            CasinoButton::DecreaseBet => {
+                bet_change_messages.write(BetChangeMessage(BetChange::Decrease));
+            }
+            CasinoButton::Choice(choice) if round.is_none() => {
+                bet_messages.write(CasinoBetMessage { choice: *choice });
+            }
+            CasinoButton::Choice(_) => (),
+        }
+    }
+}
+
+fn start_round(
+    mut commands: Commands,
+    mut bet_messages: MessageReader<CasinoBetMessage>,
+    casino_state: Res<State<CasinoState>>,
+    games: Res<CasinoGames>,
+    bet: Res<Bet>,
+    mut score: ResMut<Score>,
+    assets: Res<Assets>,
+    credit_terms: Res<bevy::asset::Assets<CreditTerms>>,
+    session: Res<Session>,
+    safeguard_settings: Res<SafeguardSettings>,
+    mut status: Query<&mut Text, With<StatusDisplay>>,
+) {
+    // Only the first bet counts, anything sent alongside it is dropped.
+    let Some(msg) = bet_messages.read().next().cloned() else {
+        return;
+    };
+    bet_messages.clear();
+    if let Some(reason) = session.limit_reached(&safeguard_settings) {
+        for mut status in &mut status {
+            **status = format!("Bet refused: {reason}");
+        }
+        return;
+    }
+    let CasinoState::Game(id) = casino_state.get() else {
+        return;
+    };
+    let valid_choice = games
+        .get(id)
+        .is_some_and(|game| msg.choice < game.choice_count);
+    // Going broke is settled with the credit terms, so the round can't start without them.
+    if score.0 == 0 || !valid_choice || !credit_terms.contains(&assets.credit_terms) {
+        return;
+    }
+    let stake = bet.0.min(score.0);
+    score.0 -= stake;
+    commands.insert_resource(CasinoRound {
+        stake,
+        choice: msg.choice,
+    });
+}
+
+/// Pays out the finished round and settles it like a gamba round, with the same credit,
+/// bankruptcy and ledger handling.
+fn settle_round(
+    mut commands: Commands,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    assets: Res<Assets>,
+    mut payout_messages: MessageReader<CasinoPayoutMessage>,
+    round: Option<Res<CasinoRound>>,
+    casino_state: Res<State<CasinoState>>,
+    games: Res<CasinoGames>,
+    mut score: ResMut<Score>,
+    mut bet: ResMut<Bet>,
+    mut stats: ResMut<Stats>,
+    credit: Res<Credit>,
+    credit_terms: Res<bevy::asset::Assets<CreditTerms>>,
+    mut result_messages: MessageWriter<BetResultMessage>,
+    mut status: Query<&mut Text, With<StatusDisplay>>,
+) {
+    let Some(terms) = credit_terms.get(&assets.credit_terms) else {
+        return;
+    };
+    for msg in payout_messages.read() {
+        let score_before = score.0 + msg.stake;
+        score.0 = score.0.saturating_add(msg.payout);
+        let (text, settlement, sound_effects) = if msg.payout > msg.stake {
+            (
+                format!("{}: won ${}", msg.outcome, msg.payout - msg.stake),
+                Settlement::Won,
+                vec![&assets.win1, &assets.win2, &assets.win3],
+            )
+        } else if msg.payout == msg.stake {
+            (
+                format!("{}: got your ${} back", msg.outcome, msg.stake),
+                Settlement::Lost,
+                vec![&assets.win1],
+            )
+        } else if score.0 > 0 {
+            (
+                format!("{}: lost ${}", msg.outcome, msg.stake - msg.payout),
+                Settlement::Lost,
+                vec![
+                    &assets.loss1,
+                    &assets.loss2,
+                    &assets.loss3,
+                    &assets.loss4,
+                    &assets.loss5,
+                    &assets.loss6,
+                    &assets.loss7,
+                    &assets.loss8,
+                    &assets.loss9,
+                ],
+            )
+        } else {
+            go_broke(
+                &mut commands,
+                &mut **rng,
+                &mut score,
+                &mut stats,
+                &credit,
+                terms,
+            );
+            (
+                format!("{}: lost ${}", msg.outcome, msg.stake - msg.payout),
+                Settlement::Bankrupt,
+                vec![
+                    &assets.bankrupt1,
+                    &assets.bankrupt2,
+                    &assets.bankrupt3,
+                    &assets.bankrupt4,
+                ],
+            )
+        };
+        commands.spawn(AudioPlayer::new(
+            (*sound_effects.iter().choose(&mut rng).unwrap()).clone(),
+        ));
+        bet.0 = bet.0.min(score.0);
+        for mut status in &mut status {
+            **status = text.clone();
+        }
+
+        let game = match casino_state.get() {
+            CasinoState::Game(id) => games.get(id),
+            CasinoState::Hub => None,
+        };
+        let choice = round.as_ref().map_or(0, |round| round.choice);
+        result_messages.write(BetResultMessage {
+            round: SettledRound::Casino {
+                game: game.map_or("casino", |game| game.name),
+                choice: game
+                    .and_then(|game| game.choices.get(choice))
+                    .map_or_else(|| format!("choice {}", choice + 1), |label| (*label).into()),
+                outcome: msg.outcome.clone(),
+            },
+            stake: msg.stake,
+            score_before,
+            score_after: score.0,
+            settlement,
+        });
+        commands.remove_resource::<CasinoRound>();
+    }
+}
+
+/// Hands the stake of an unfinished round back when leaving its game. Navigation is blocked
+/// while a round is running, so this only catches rounds cut short some other way.
+fn abandon_round(
+    mut commands: Commands,
+    round: Option<Res<CasinoRound>>,
+    mut score: ResMut<Score>,
+) {
+    if let Some(round) = round {
+        score.0 += round.stake;
+        commands.remove_resource::<CasinoRound>();
+    }
+}
+
+fn update_casino_displays(
+    score: Res<Score>,
+    bet: Res<Bet>,
+    mut score_query: Query<&mut TextSpan, (With<ScoreDisplay>, Without<BetDisplay>)>,
+    mut bet_query: Query<&mut TextSpan, (With<BetDisplay>, Without<ScoreDisplay>)>,
+) {
+    for mut span in &mut score_query {
+        **span = format!("{}", score.0);
+    }
+    for mut span in &mut bet_query {
+        **span = format!("{}", bet.0);
+    }
+}
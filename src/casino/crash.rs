@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::{
+    assets::Assets,
+    casino::{CasinoAppExt, CasinoGame, CasinoPayoutMessage, CasinoRound, CasinoState, playing},
+    util::despawn_all,
+};
+
+/// A multiplier that climbs until it crashes, cash out before it does.
+pub struct CrashPlugin;
+
+impl Plugin for CrashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_casino_game::<Crash>()
+            .add_systems(OnEnter(CasinoState::Game(Crash::ID.into())), setup)
+            .add_systems(
+                Update,
+                (
+                    launch.run_if(resource_added::<CasinoRound>),
+                    climb.run_if(resource_exists::<Flight>),
+                )
+                    .chain()
+                    .run_if(playing::<Crash>),
+            )
+            .add_systems(
+                OnExit(CasinoState::Game(Crash::ID.into())),
+                (despawn_all::<OnCrashScreen>, stop_flight),
+            );
+    }
+}
+
+struct Crash;
+
+impl CasinoGame for Crash {
+    const ID: &'static str = "crash";
+    const NAME: &'static str = "crash";
+    const DESCRIPTION: &'static str =
+        "the amogus flies higher and higher, cash out before it crashes";
+    const CHOICES: &'static [&'static str] = &[
+        "cash out at 1.5x",
+        "cash out at 2x",
+        "cash out at 3x",
+        "cash out at 10x",
+    ];
+}
+
+/// Cash-out multiplier of each choice.
+const TARGETS: [f32; 4] = [1.5, 2., 3., 10.];
+/// Share of every bet the house keeps, on average.
+const HOUSE_EDGE: f32 = 0.03;
+const MAX_MULTIPLIER: f32 = 100.;
+/// The multiplier grows by e^(GROWTH_RATE * seconds).
+const GROWTH_RATE: f32 = 0.35;
+const AMOGUS_INDEX: usize = 20;
+/// How far up the screen the amogus is at 10x, in percent.
+const FLIGHT_HEIGHT: f32 = 50.;
+
+#[derive(Component)]
+struct OnCrashScreen;
+
+#[derive(Component)]
+struct MultiplierDisplay;
+
+#[derive(Component)]
+struct Rocket;
+
+#[derive(Resource)]
+struct Flight {
+    crash_at: f32,
+    cash_out_at: f32,
+    elapsed: f32,
+}
+
+fn setup(mut commands: Commands, assets: Res<Assets>) {
+    commands.spawn((
+        Node {
+            justify_content: JustifyContent::Center,
+            width: Val::Percent(100.),
+            position_type: PositionType::Absolute,
+            top: Val::Percent(15.),
+            ..default()
+        },
+        OnCrashScreen,
+        children![(
+            Text::new("1.00x"),
+            TextFont {
+                font_size: 48.,
+                ..default()
+            },
+            MultiplierDisplay,
+        )],
+    ));
+    commands.spawn((
+        Node {
+            width: Val::Px(64.),
+            height: Val::Px(64.),
+            position_type: PositionType::Absolute,
+            left: Val::Percent(20.),
+            bottom: Val::Percent(25.),
+            ..default()
+        },
+        ImageNode::from_atlas_image(
+            assets.textures.clone(),
+            TextureAtlas {
+                layout: assets.texture_atlas.clone(),
+                index: AMOGUS_INDEX,
+            },
+        ),
+        Rocket,
+        OnCrashScreen,
+    ));
+}
+
+/// Decides the crash point as soon as the bet comes in. A target of `m` survives with a chance
+/// of `(1 - HOUSE_EDGE) / m`.
+fn launch(
+    mut commands: Commands,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    round: Res<CasinoRound>,
+) {
+    let roll = rng.next_u32() as f32 / u32::MAX as f32;
+    let crash_at = ((1. - HOUSE_EDGE) / (1. - roll).max(f32::EPSILON)).clamp(1., MAX_MULTIPLIER);
+    commands.insert_resource(Flight {
+        crash_at: (crash_at * 100.).floor() / 100.,
+        cash_out_at: TARGETS[round.choice],
+        elapsed: 0.,
+    });
+}
+
+fn climb(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flight: ResMut<Flight>,
+    round: Res<CasinoRound>,
+    mut display: Single<(&mut Text, &mut TextColor), With<MultiplierDisplay>>,
+    mut rocket: Single<&mut Node, With<Rocket>>,
+    mut payout_messages: MessageWriter<CasinoPayoutMessage>,
+) {
+    flight.elapsed += time.delta_secs();
+    let end = flight.crash_at.min(flight.cash_out_at);
+    let multiplier = (GROWTH_RATE * flight.elapsed).exp().min(end);
+    let (text, color) = &mut *display;
+    ***text = format!("{multiplier:.2}x");
+    color.0 = Color::WHITE;
+    rocket.left = Val::Percent(20. + multiplier.log10() * 60.);
+    rocket.bottom = Val::Percent(25. + multiplier.log10() * FLIGHT_HEIGHT);
+    if multiplier < end {
+        return;
+    }
+
+    let cashed_out = flight.cash_out_at <= flight.crash_at;
+    color.0 = if cashed_out {
+        Color::srgb_u8(0, 200, 64)
+    } else {
+        Color::srgb_u8(220, 48, 48)
+    };
+    payout_messages.write(CasinoPayoutMessage {
+        stake: round.stake,
+        payout: if cashed_out {
+            (round.stake as f32 * flight.cash_out_at).floor() as u64
+        } else {
+            0
+        },
+        outcome: if cashed_out {
+            format!("cashed out at {:.2}x", flight.cash_out_at)
+        } else {
+            format!("crashed at {:.2}x", flight.crash_at)
+        },
+    });
+    commands.remove_resource::<Flight>();
+}
+
+fn stop_flight(mut commands: Commands) {
+    commands.remove_resource::<Flight>();
+}
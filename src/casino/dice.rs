@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::{
+    casino::{CasinoAppExt, CasinoGame, CasinoPayoutMessage, CasinoRound, CasinoState, playing},
+    util::despawn_all,
+};
+
+/// Two dice, bet on whether their sum comes out under, at or over 7.
+pub struct DicePlugin;
+
+impl Plugin for DicePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_casino_game::<Dice>()
+            .add_systems(OnEnter(CasinoState::Game(Dice::ID.into())), setup)
+            .add_systems(
+                Update,
+                (
+                    roll.run_if(resource_added::<CasinoRound>),
+                    tumble.run_if(resource_exists::<Roll>),
+                )
+                    .chain()
+                    .run_if(playing::<Dice>),
+            )
+            .add_systems(
+                OnExit(CasinoState::Game(Dice::ID.into())),
+                (despawn_all::<OnDiceScreen>, stop_rolling),
+            );
+    }
+}
+
+struct Dice;
+
+impl CasinoGame for Dice {
+    const ID: &'static str = "dice";
+    const NAME: &'static str = "dice over/under";
+    const DESCRIPTION: &'static str = "roll two dice, is the sum under, at or over 7?";
+    const CHOICES: &'static [&'static str] = &["under 7 (2x)", "exactly 7 (5x)", "over 7 (2x)"];
+}
+
+/// Multiple of the stake paid back for each choice.
+const PAYOUTS: [u64; 3] = [2, 5, 2];
+const ROLL_SECS: f32 = 1.;
+/// How long each face is shown while the dice tumble.
+const FACE_SECS: f32 = 0.08;
+
+#[derive(Component)]
+struct OnDiceScreen;
+
+/// One of the two dice, by index.
+#[derive(Component)]
+struct Die(usize);
+
+#[derive(Resource)]
+struct Roll {
+    dice: [u32; 2],
+    timer: Timer,
+}
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(24.),
+                width: Val::Percent(100.),
+                height: Val::Percent(50.),
+                position_type: PositionType::Absolute,
+                top: Val::Percent(15.),
+                ..default()
+            },
+            OnDiceScreen,
+        ))
+        .with_children(|parent| {
+            for die in 0..2 {
+                parent.spawn((
+                    Node {
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        width: Val::Px(96.),
+                        height: Val::Px(96.),
+                        ..default()
+                    },
+                    BorderRadius::all(Val::Px(12.)),
+                    BackgroundColor(Color::srgb_u8(243, 243, 243)),
+                    children![(
+                        Text::new("?"),
+                        TextFont {
+                            font_size: 48.,
+                            ..default()
+                        },
+                        TextColor(Color::srgb_u8(29, 29, 29)),
+                        Die(die),
+                    )],
+                ));
+            }
+        });
+}
+
+/// Decides the dice as soon as the bet comes in, they tumble for a bit before showing it.
+fn roll(mut commands: Commands, mut rng: Single<&mut WyRand, With<GlobalRng>>) {
+    commands.insert_resource(Roll {
+        dice: [rng.next_u32() % 6 + 1, rng.next_u32() % 6 + 1],
+        timer: Timer::from_seconds(ROLL_SECS, TimerMode::Once),
+    });
+}
+
+fn tumble(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut roll: ResMut<Roll>,
+    round: Res<CasinoRound>,
+    mut faces: Query<(&mut Text, &Die)>,
+    mut payout_messages: MessageWriter<CasinoPayoutMessage>,
+) {
+    roll.timer.tick(time.delta());
+    if !roll.timer.is_finished() {
+        // Cycles through the faces without touching the RNG, replays depend on its sequence.
+        let step = (roll.timer.elapsed_secs() / FACE_SECS) as u32;
+        for (mut text, die) in &mut faces {
+            **text = format!("{}", (step + die.0 as u32 * 3) % 6 + 1);
+        }
+        return;
+    }
+
+    for (mut text, die) in &mut faces {
+        **text = format!("{}", roll.dice[die.0]);
+    }
+    let sum = roll.dice[0] + roll.dice[1];
+    let won = match round.choice {
+        0 => sum < 7,
+        1 => sum == 7,
+        _ => sum > 7,
+    };
+    payout_messages.write(CasinoPayoutMessage {
+        stake: round.stake,
+        payout: if won {
+            round.stake * PAYOUTS[round.choice]
+        } else {
+            0
+        },
+        outcome: format!("rolled {} + {} = {sum}", roll.dice[0], roll.dice[1]),
+    });
+    commands.remove_resource::<Roll>();
+}
+
+fn stop_rolling(mut commands: Commands) {
+    commands.remove_resource::<Roll>();
+}
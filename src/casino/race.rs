@@ -0,0 +1,500 @@
+use bevy::prelude::*;
+use bevy_rand::prelude::*;
+use rand_core::{RngCore, SeedableRng};
+
+use crate::{
+    animation::AnimationClips,
+    assets::Assets,
+    casino::{
+        CasinoAppExt, CasinoBetMessage, CasinoGame, CasinoPayoutMessage, CasinoRound, CasinoState,
+        playing, round_open,
+    },
+    gamba::spawn_pond,
+    replay::live_input,
+    util::despawn_all,
+};
+
+/// The five amogi race across the pond, bet on the winner, a top-2 finish or the exact order
+/// of the first three.
+pub struct RacePlugin;
+
+impl Plugin for RacePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_casino_game::<Race>()
+            .add_systems(
+                OnEnter(CasinoState::Game(Race::ID.into())),
+                (draw_field.run_if(not(resource_exists::<Field>)), setup).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    handle_race_buttons.run_if(live_input.and(round_open)),
+                    start_race.run_if(resource_added::<CasinoRound>),
+                    run_race.run_if(resource_exists::<Racing>),
+                    update_board,
+                )
+                    .chain()
+                    .run_if(playing::<Race>),
+            )
+            .add_systems(
+                OnExit(CasinoState::Game(Race::ID.into())),
+                (despawn_all::<OnRaceScreen>, stop_race),
+            );
+    }
+}
+
+struct Race;
+
+impl CasinoGame for Race {
+    const ID: &'static str = "race";
+    const NAME: &'static str = "amogus race";
+    const DESCRIPTION: &'static str = "five amogi race across the pond, pick the winner";
+    const CHOICES: &'static [&'static str] = &[
+        "red wins",
+        "yellow wins",
+        "green wins",
+        "blue wins",
+        "pink wins",
+        "red top 2",
+        "yellow top 2",
+        "green top 2",
+        "blue top 2",
+        "pink top 2",
+    ];
+    /// The exact order of the first three comes after the buttons, see [`encode_order`].
+    const CHOICE_COUNT: usize = 2 * RACERS + ORDERS;
+}
+
+const RACERS: usize = 5;
+const RACER_NAMES: [&str; RACERS] = ["red", "yellow", "green", "blue", "pink"];
+/// Number of ways to pick the first three out of the racers, in order.
+const ORDERS: usize = RACERS * (RACERS - 1) * (RACERS - 2);
+const AMOGUS_INDEX: usize = 20;
+const AMOGUS_SCALE: Vec3 = Vec3::splat(2.);
+
+const POND_POS: Vec2 = Vec2::new(0., 60.);
+const LANE_SPACING: f32 = 50.;
+const START_X: f32 = -280.;
+const TRACK_LENGTH: f32 = 560.;
+/// How often the race is simulated, playback interpolates between ticks.
+const TICK_SECS: f32 = 0.1;
+/// Pond units per second of a racer with a rating of 1.
+const BASE_SPEED: f32 = 90.;
+/// Ratings are drawn from this range for every race.
+const RATING_RANGE: (f32, f32) = (0.85, 1.15);
+/// Chance per tick of slipping on a lily pad and standing still for a while.
+const SLIP_CHANCE: f32 = 0.03;
+const SLIP_TICKS: u32 = 6;
+/// Chance per tick of a splash that pushes the racer forward for a while.
+const BOOST_CHANCE: f32 = 0.03;
+const BOOST_TICKS: u32 = 8;
+const BOOST_SPEED: f32 = 1.6;
+/// Races simulated to work out the published odds.
+const ODDS_SAMPLES: usize = 500;
+/// Share of every bet the house keeps, on average.
+const HOUSE_EDGE: f32 = 0.1;
+const MIN_ODDS: f32 = 1.1;
+
+#[derive(Component, Clone)]
+struct OnRaceScreen;
+
+#[derive(Component)]
+struct Racer(usize);
+
+#[derive(Component)]
+enum RaceButton {
+    /// Adds a racer to the exact order being picked.
+    Pick(usize),
+    ClearPick,
+    BetOrder,
+}
+
+#[derive(Component)]
+struct BoardDisplay;
+
+#[derive(Component)]
+struct PickDisplay;
+
+/// The racers of the next race and the odds published for them.
+#[derive(Resource)]
+struct Field {
+    number: u64,
+    ratings: [f32; RACERS],
+    /// Chance of each racer winning, estimated by simulating the race.
+    win_chances: [f32; RACERS],
+}
+
+impl Field {
+    fn new(number: u64, seed: u64) -> Self {
+        let mut rng = WyRand::seed_from_u64(seed);
+        let (low, high) = RATING_RANGE;
+        let ratings = std::array::from_fn(|_| low + unit(&mut rng) * (high - low));
+        let mut wins = [1.; RACERS];
+        for _ in 0..ODDS_SAMPLES {
+            wins[simulate(&ratings, &mut rng).order[0]] += 1.;
+        }
+        let total = (ODDS_SAMPLES + RACERS) as f32;
+        Self {
+            number,
+            ratings,
+            win_chances: wins.map(|wins| wins / total),
+        }
+    }
+
+    fn top_two_chance(&self, racer: usize) -> f32 {
+        let p = &self.win_chances;
+        p[racer]
+            + (0..RACERS)
+                .filter(|other| *other != racer)
+                .map(|other| p[other] * p[racer] / (1. - p[other]))
+                .sum::<f32>()
+    }
+
+    /// Chance of `order` being the first three, treating every place like a smaller race.
+    fn order_chance(&self, order: [usize; 3]) -> f32 {
+        let p = &self.win_chances;
+        p[order[0]] * p[order[1]] / (1. - p[order[0]]) * p[order[2]]
+            / (1. - p[order[0]] - p[order[1]])
+    }
+
+    fn chance(&self, choice: usize) -> f32 {
+        match choice {
+            c if c < RACERS => self.win_chances[c],
+            c if c < 2 * RACERS => self.top_two_chance(c - RACERS),
+            c => self.order_chance(decode_order(c - 2 * RACERS)),
+        }
+    }
+
+    /// Published decimal odds of a choice, the payout per $1 staked.
+    fn odds(&self, choice: usize) -> f32 {
+        ((1. - HOUSE_EDGE) / self.chance(choice) * 10.)
+            .floor()
+            .max(MIN_ODDS * 10.)
+            / 10.
+    }
+}
+
+/// Every tick of a simulated race.
+struct RaceRun {
+    positions: Vec<[f32; RACERS]>,
+    slipping: Vec<[bool; RACERS]>,
+    order: [usize; RACERS],
+}
+
+/// A uniformly distributed number in `0..1`.
+fn unit(rng: &mut WyRand) -> f32 {
+    rng.next_u32() as f32 / (u32::MAX as f32 + 1.)
+}
+
+fn simulate(ratings: &[f32; RACERS], rng: &mut WyRand) -> RaceRun {
+    let mut position = [0.; RACERS];
+    let mut slip = [0; RACERS];
+    let mut boost = [0; RACERS];
+    // Tick and overshoot past the finish line of every racer that made it.
+    let mut finished: [Option<(usize, f32)>; RACERS] = [None; RACERS];
+    let mut run = RaceRun {
+        positions: vec![position],
+        slipping: vec![[false; RACERS]],
+        order: [0; RACERS],
+    };
+    let mut tick = 0;
+    while finished.iter().any(Option::is_none) {
+        tick += 1;
+        for racer in 0..RACERS {
+            if finished[racer].is_some() {
+                continue;
+            }
+            if slip[racer] == 0 && boost[racer] == 0 {
+                let roll = unit(rng);
+                if roll < SLIP_CHANCE {
+                    slip[racer] = SLIP_TICKS;
+                } else if roll < SLIP_CHANCE + BOOST_CHANCE {
+                    boost[racer] = BOOST_TICKS;
+                }
+            }
+            let speed = if slip[racer] > 0 {
+                slip[racer] -= 1;
+                0.
+            } else if boost[racer] > 0 {
+                boost[racer] -= 1;
+                BOOST_SPEED
+            } else {
+                0.7 + unit(rng) * 0.6
+            };
+            position[racer] += BASE_SPEED * ratings[racer] * speed * TICK_SECS;
+            if position[racer] >= TRACK_LENGTH {
+                finished[racer] = Some((tick, position[racer] - TRACK_LENGTH));
+            }
+        }
+        run.positions.push(position.map(|p| p.min(TRACK_LENGTH)));
+        run.slipping.push(slip.map(|ticks| ticks > 0));
+    }
+
+    let mut order: [usize; RACERS] = std::array::from_fn(|racer| racer);
+    order.sort_by(|a, b| {
+        let (tick_a, over_a) = finished[*a].unwrap_or_default();
+        let (tick_b, over_b) = finished[*b].unwrap_or_default();
+        tick_a.cmp(&tick_b).then(over_b.total_cmp(&over_a))
+    });
+    run.order = order;
+    run
+}
+
+/// Index of the first three places among the [`ORDERS`] exact-order choices.
+fn encode_order(order: [usize; 3]) -> usize {
+    let second = order[1] - (order[1] > order[0]) as usize;
+    let third = order[2] - (order[2] > order[0]) as usize - (order[2] > order[1]) as usize;
+    (order[0] * (RACERS - 1) + second) * (RACERS - 2) + third
+}
+
+fn decode_order(index: usize) -> [usize; 3] {
+    let mut left: Vec<usize> = (0..RACERS).collect();
+    let first = left.remove(index / ((RACERS - 1) * (RACERS - 2)));
+    let second = left.remove(index / (RACERS - 2) % (RACERS - 1));
+    let third = left.remove(index % (RACERS - 2));
+    [first, second, third]
+}
+
+/// Racers picked so far for an exact-order bet.
+#[derive(Resource, Default)]
+struct OrderPick(Vec<usize>);
+
+/// The race being run and played back.
+#[derive(Resource)]
+struct Racing {
+    run: RaceRun,
+    elapsed: f32,
+    /// Seed of the field after this race, drawn with the race so playback speed can't change it.
+    next_field_seed: u64,
+}
+
+fn lane_y(racer: usize) -> f32 {
+    POND_POS.y + (racer as f32 - (RACERS - 1) as f32 / 2.) * LANE_SPACING
+}
+
+fn draw_field(mut commands: Commands, mut rng: Single<&mut WyRand, With<GlobalRng>>) {
+    commands.insert_resource(Field::new(1, rng.next_u64()));
+}
+
+fn setup(mut commands: Commands, assets: Res<Assets>, clips: AnimationClips) {
+    commands.init_resource::<OrderPick>();
+    spawn_pond(&mut commands, &assets, &clips, POND_POS, OnRaceScreen);
+
+    // Finish line
+    commands.spawn((
+        Sprite::from_color(Color::WHITE, Vec2::new(4., LANE_SPACING * RACERS as f32)),
+        OnRaceScreen,
+        Transform::from_xyz(START_X + TRACK_LENGTH, POND_POS.y, 5.),
+    ));
+
+    for racer in 0..RACERS {
+        commands.spawn((
+            Sprite::from_atlas_image(
+                assets.textures.clone(),
+                TextureAtlas::from(assets.texture_atlas.clone()).with_index(AMOGUS_INDEX + racer),
+            ),
+            Racer(racer),
+            OnRaceScreen,
+            Transform::from_xyz(START_X, lane_y(racer), 6.).with_scale(AMOGUS_SCALE),
+        ));
+    }
+
+    commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.),
+                padding: UiRect::all(Val::Px(8.)),
+                position_type: PositionType::Absolute,
+                top: Val::Px(40.),
+                right: Val::Px(0.),
+                ..default()
+            },
+            BackgroundColor(Color::srgba_u8(29, 29, 29, 160)),
+            OnRaceScreen,
+            children![(
+                Text::default(),
+                TextFont {
+                    font_size: 14.,
+                    ..default()
+                },
+                BoardDisplay,
+            )],
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(Node {
+                    column_gap: Val::Px(4.),
+                    ..default()
+                })
+                .with_children(|row| {
+                    for (racer, name) in RACER_NAMES.iter().enumerate() {
+                        row.spawn(race_button(RaceButton::Pick(racer), name));
+                    }
+                });
+            parent.spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 14.,
+                    ..default()
+                },
+                PickDisplay,
+            ));
+            parent.spawn((
+                Node {
+                    column_gap: Val::Px(4.),
+                    ..default()
+                },
+                children![
+                    race_button(RaceButton::BetOrder, "bet on this order"),
+                    race_button(RaceButton::ClearPick, "clear"),
+                ],
+            ));
+        });
+}
+
+fn race_button(action: RaceButton, label: &str) -> impl Bundle + use<> {
+    (
+        Button,
+        action,
+        Node {
+            padding: UiRect::all(Val::Px(4.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgb_u8(69, 69, 69)),
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size: 14.,
+                ..default()
+            },
+        )],
+    )
+}
+
+fn handle_race_buttons(
+    interaction_query: Query<(&Interaction, &RaceButton), Changed<Interaction>>,
+    mut pick: ResMut<OrderPick>,
+    mut bet_messages: MessageWriter<CasinoBetMessage>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            RaceButton::Pick(racer) => {
+                if pick.0.len() < 3 && !pick.0.contains(racer) {
+                    pick.0.push(*racer);
+                }
+            }
+            RaceButton::ClearPick => pick.0.clear(),
+            RaceButton::BetOrder => {
+                if let [first, second, third] = pick.0[..] {
+                    bet_messages.write(CasinoBetMessage {
+                        choice: 2 * RACERS + encode_order([first, second, third]),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Runs the whole race as soon as the bet comes in, it is only played back afterwards.
+fn start_race(
+    mut commands: Commands,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    field: Res<Field>,
+) {
+    let mut race_rng = WyRand::seed_from_u64(rng.next_u64());
+    commands.insert_resource(Racing {
+        run: simulate(&field.ratings, &mut race_rng),
+        elapsed: 0.,
+        next_field_seed: rng.next_u64(),
+    });
+}
+
+fn run_race(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut racing: ResMut<Racing>,
+    mut field: ResMut<Field>,
+    round: Res<CasinoRound>,
+    mut racers: Query<(&mut Transform, &Racer)>,
+    mut payout_messages: MessageWriter<CasinoPayoutMessage>,
+) {
+    racing.elapsed += time.delta_secs();
+    let ticks = racing.run.positions.len() - 1;
+    let tick = (racing.elapsed / TICK_SECS).min(ticks as f32);
+    let (previous, next) = (tick.floor() as usize, (tick.ceil() as usize).min(ticks));
+    for (mut transform, racer) in &mut racers {
+        let position = racing.run.positions[previous][racer.0]
+            .lerp(racing.run.positions[next][racer.0], tick.fract());
+        transform.translation.x = START_X + position;
+        // Slipping racers tip over.
+        let angle = if racing.run.slipping[next][racer.0] {
+            0.6
+        } else {
+            0.
+        };
+        transform.rotation = Quat::from_rotation_z(angle);
+    }
+    if tick < ticks as f32 {
+        return;
+    }
+
+    let order = racing.run.order;
+    let won = match round.choice {
+        c if c < RACERS => order[0] == c,
+        c if c < 2 * RACERS => order[..2].contains(&(c - RACERS)),
+        c => decode_order(c - 2 * RACERS) == [order[0], order[1], order[2]],
+    };
+    payout_messages.write(CasinoPayoutMessage {
+        stake: round.stake,
+        payout: if won {
+            (round.stake as f32 * field.odds(round.choice)).floor() as u64
+        } else {
+            0
+        },
+        outcome: format!(
+            "{}, {} and {} finished first",
+            RACER_NAMES[order[0]], RACER_NAMES[order[1]], RACER_NAMES[order[2]]
+        ),
+    });
+    *field = Field::new(field.number + 1, racing.next_field_seed);
+    commands.remove_resource::<Racing>();
+}
+
+fn stop_race(mut commands: Commands) {
+    commands.remove_resource::<Racing>();
+}
+
+fn update_board(
+    field: Res<Field>,
+    pick: Res<OrderPick>,
+    mut board: Single<&mut Text, (With<BoardDisplay>, Without<PickDisplay>)>,
+    mut pick_display: Single<&mut Text, (With<PickDisplay>, Without<BoardDisplay>)>,
+    spawned: Query<(), Added<BoardDisplay>>,
+) {
+    if field.is_changed() || !spawned.is_empty() {
+        let mut text = format!("race #{}\nodds:   win   top 2\n", field.number);
+        for (racer, name) in RACER_NAMES.iter().enumerate() {
+            text += &format!(
+                "{name}: {:.1}x  {:.1}x\n",
+                field.odds(racer),
+                field.odds(RACERS + racer)
+            );
+        }
+        ***board = text;
+    }
+    if pick.is_changed() || !spawned.is_empty() {
+        let names: Vec<&str> = pick.0.iter().map(|racer| RACER_NAMES[*racer]).collect();
+        ***pick_display = match pick.0[..] {
+            [first, second, third] => format!(
+                "exact order: {} ({:.1}x)",
+                names.join(", "),
+                field.odds(2 * RACERS + encode_order([first, second, third]))
+            ),
+            _ => format!("exact order: {}", names.join(", ")),
+        };
+    }
+}
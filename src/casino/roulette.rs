@@ -0,0 +1,233 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::{
+    casino::{CasinoAppExt, CasinoGame, CasinoPayoutMessage, CasinoRound, CasinoState, playing},
+    util::despawn_all,
+};
+
+/// A small roulette wheel with a single green zero and twelve red and black pockets.
+pub struct RoulettePlugin;
+
+impl Plugin for RoulettePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_casino_game::<Roulette>()
+            .init_resource::<Wheel>()
+            .add_systems(OnEnter(CasinoState::Game(Roulette::ID.into())), setup)
+            .add_systems(
+                Update,
+                (
+                    spin_wheel.run_if(resource_added::<CasinoRound>),
+                    roll_ball.run_if(resource_exists::<Spin>),
+                )
+                    .chain()
+                    .run_if(playing::<Roulette>),
+            )
+            .add_systems(
+                OnExit(CasinoState::Game(Roulette::ID.into())),
+                (despawn_all::<OnRouletteScreen>, stop_spinning),
+            );
+    }
+}
+
+struct Roulette;
+
+impl CasinoGame for Roulette {
+    const ID: &'static str = "roulette";
+    const NAME: &'static str = "mini roulette";
+    const DESCRIPTION: &'static str = "13 pockets, bet on red, black or the green zero";
+    const CHOICES: &'static [&'static str] = &["red (2x)", "black (2x)", "green (12x)"];
+}
+
+const POCKETS: usize = 13;
+/// Multiple of the stake paid back for each choice.
+const PAYOUTS: [u64; 3] = [2, 2, 12];
+const WHEEL_RADIUS: f32 = 120.;
+const POCKET_SIZE: f32 = 40.;
+const BALL_SIZE: f32 = 14.;
+/// Full laps the ball makes before settling into its pocket.
+const SPIN_LAPS: usize = 3;
+const SPIN_SECS: f32 = 3.;
+
+#[derive(Component)]
+struct OnRouletteScreen;
+
+#[derive(Component)]
+struct Ball;
+
+/// Pocket the ball last stopped in, the next spin starts from there.
+#[derive(Resource, Default)]
+struct Wheel {
+    ball: usize,
+}
+
+#[derive(Resource)]
+struct Spin {
+    start: usize,
+    /// Pockets the ball travels, ending on the winning one.
+    steps: usize,
+    timer: Timer,
+}
+
+#[derive(PartialEq)]
+enum PocketColor {
+    Red,
+    Black,
+    Green,
+}
+
+impl PocketColor {
+    fn of(pocket: usize) -> Self {
+        match pocket {
+            0 => PocketColor::Green,
+            _ if !pocket.is_multiple_of(2) => PocketColor::Red,
+            _ => PocketColor::Black,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            PocketColor::Red => "red",
+            PocketColor::Black => "black",
+            PocketColor::Green => "green",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            PocketColor::Red => Color::srgb_u8(200, 32, 32),
+            PocketColor::Black => Color::srgb_u8(29, 29, 29),
+            PocketColor::Green => Color::srgb_u8(0, 128, 48),
+        }
+    }
+}
+
+/// Top left corner of a node of `size` centred on `pocket`, relative to the wheel's centre.
+fn pocket_position(pocket: usize, size: f32) -> Vec2 {
+    Vec2::from_angle(pocket as f32 / POCKETS as f32 * TAU - TAU / 4.) * WHEEL_RADIUS
+        - Vec2::splat(size / 2.)
+}
+
+fn setup(mut commands: Commands, wheel: Res<Wheel>) {
+    let ball = pocket_position(wheel.ball, BALL_SIZE);
+    commands
+        .spawn((
+            Node {
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                width: Val::Percent(100.),
+                height: Val::Percent(50.),
+                position_type: PositionType::Absolute,
+                top: Val::Percent(15.),
+                ..default()
+            },
+            OnRouletteScreen,
+        ))
+        .with_children(|parent| {
+            // Zero-sized anchor in the middle that the pockets are placed around.
+            parent.spawn(Node::default()).with_children(|center| {
+                for pocket in 0..POCKETS {
+                    let position = pocket_position(pocket, POCKET_SIZE);
+                    center.spawn((
+                        Node {
+                            align_items: AlignItems::Center,
+                            justify_content: JustifyContent::Center,
+                            width: Val::Px(POCKET_SIZE),
+                            height: Val::Px(POCKET_SIZE),
+                            position_type: PositionType::Absolute,
+                            left: Val::Px(position.x),
+                            top: Val::Px(position.y),
+                            ..default()
+                        },
+                        BorderRadius::MAX,
+                        BackgroundColor(PocketColor::of(pocket).color()),
+                        children![(
+                            Text::new(format!("{pocket}")),
+                            TextFont {
+                                font_size: 16.,
+                                ..default()
+                            },
+                        )],
+                    ));
+                }
+                center.spawn((
+                    Node {
+                        width: Val::Px(BALL_SIZE),
+                        height: Val::Px(BALL_SIZE),
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(ball.x),
+                        top: Val::Px(ball.y),
+                        ..default()
+                    },
+                    BorderRadius::MAX,
+                    BackgroundColor(Color::WHITE),
+                    ZIndex(1),
+                    Ball,
+                ));
+            });
+        });
+}
+
+/// Decides the winning pocket as soon as the bet comes in.
+fn spin_wheel(
+    mut commands: Commands,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    wheel: Res<Wheel>,
+) {
+    let pocket = rng.next_u32() as usize % POCKETS;
+    commands.insert_resource(Spin {
+        start: wheel.ball,
+        steps: SPIN_LAPS * POCKETS + (pocket + POCKETS - wheel.ball) % POCKETS,
+        timer: Timer::from_seconds(SPIN_SECS, TimerMode::Once),
+    });
+}
+
+fn roll_ball(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spin: ResMut<Spin>,
+    mut wheel: ResMut<Wheel>,
+    round: Res<CasinoRound>,
+    mut ball: Query<&mut Node, With<Ball>>,
+    mut payout_messages: MessageWriter<CasinoPayoutMessage>,
+) {
+    spin.timer.tick(time.delta());
+    let travelled = EasingCurve::new(0., spin.steps as f32, EaseFunction::CubicOut)
+        .sample_clamped(spin.timer.fraction())
+        .round() as usize;
+    let pocket = (spin.start + travelled) % POCKETS;
+    let position = pocket_position(pocket, BALL_SIZE);
+    for mut node in &mut ball {
+        node.left = Val::Px(position.x);
+        node.top = Val::Px(position.y);
+    }
+    if !spin.timer.is_finished() {
+        return;
+    }
+
+    wheel.ball = pocket;
+    let color = PocketColor::of(pocket);
+    let won = color
+        == match round.choice {
+            0 => PocketColor::Red,
+            1 => PocketColor::Black,
+            _ => PocketColor::Green,
+        };
+    payout_messages.write(CasinoPayoutMessage {
+        stake: round.stake,
+        payout: if won {
+            round.stake * PAYOUTS[round.choice]
+        } else {
+            0
+        },
+        outcome: format!("{pocket} {}", color.name()),
+    });
+    commands.remove_resource::<Spin>();
+}
+
+fn stop_spinning(mut commands: Commands) {
+    commands.remove_resource::<Spin>();
+}
@@ -0,0 +1,169 @@
+use bevy::prelude::*;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::{
+    assets::Assets,
+    casino::{CasinoAppExt, CasinoGame, CasinoPayoutMessage, CasinoRound, CasinoState, playing},
+    util::despawn_all,
+};
+
+/// A three-reel slot machine with symbols from the texture atlas.
+pub struct SlotsPlugin;
+
+impl Plugin for SlotsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_casino_game::<Slots>()
+            .add_systems(OnEnter(CasinoState::Game(Slots::ID.into())), setup)
+            .add_systems(
+                Update,
+                (
+                    pull_lever.run_if(resource_added::<CasinoRound>),
+                    spin_reels.run_if(resource_exists::<Spin>),
+                )
+                    .chain()
+                    .run_if(playing::<Slots>),
+            )
+            .add_systems(
+                OnExit(CasinoState::Game(Slots::ID.into())),
+                (despawn_all::<OnSlotsScreen>, stop_spinning),
+            );
+    }
+}
+
+struct Slots;
+
+impl CasinoGame for Slots {
+    const ID: &'static str = "slots";
+    const NAME: &'static str = "slot machine";
+    const DESCRIPTION: &'static str = "three of a kind pays big, a pair gets the bet back";
+    const CHOICES: &'static [&'static str] = &["spin"];
+}
+
+/// Atlas index, name and three-of-a-kind multiplier of every symbol.
+const SYMBOLS: [(usize, &str, u64); 6] = [
+    (70, "pickle", 40),
+    (0, "cake", 25),
+    (20, "amogus", 15),
+    (30, "flame", 10),
+    (50, "cane", 8),
+    (60, "sign", 7),
+];
+const REEL_COUNT: usize = 3;
+/// When each reel stops, counted from the pull of the lever.
+const REEL_STOP_SECS: [f32; REEL_COUNT] = [0.8, 1.2, 1.6];
+/// How long each symbol is shown while a reel spins.
+const SYMBOL_SECS: f32 = 0.06;
+const REEL_SIZE: f32 = 96.;
+
+#[derive(Component)]
+struct OnSlotsScreen;
+
+/// One of the reels, by index.
+#[derive(Component)]
+struct Reel(usize);
+
+#[derive(Resource)]
+struct Spin {
+    /// Index into [`SYMBOLS`] each reel stops on.
+    symbols: [usize; REEL_COUNT],
+    timer: Timer,
+}
+
+fn setup(mut commands: Commands, assets: Res<Assets>) {
+    commands
+        .spawn((
+            Node {
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(12.),
+                width: Val::Percent(100.),
+                height: Val::Percent(50.),
+                position_type: PositionType::Absolute,
+                top: Val::Percent(15.),
+                ..default()
+            },
+            OnSlotsScreen,
+        ))
+        .with_children(|parent| {
+            // Each reel starts out showing a different symbol.
+            for (reel, (symbol, _, _)) in SYMBOLS.iter().enumerate().take(REEL_COUNT) {
+                parent.spawn((
+                    Node {
+                        width: Val::Px(REEL_SIZE),
+                        height: Val::Px(REEL_SIZE),
+                        padding: UiRect::all(Val::Px(8.)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb_u8(243, 243, 243)),
+                    children![(
+                        Node {
+                            width: Val::Percent(100.),
+                            height: Val::Percent(100.),
+                            ..default()
+                        },
+                        ImageNode::from_atlas_image(
+                            assets.textures.clone(),
+                            TextureAtlas {
+                                layout: assets.texture_atlas.clone(),
+                                index: *symbol,
+                            },
+                        ),
+                        Reel(reel),
+                    )],
+                ));
+            }
+        });
+}
+
+/// Decides where the reels stop as soon as the bet comes in.
+fn pull_lever(mut commands: Commands, mut rng: Single<&mut WyRand, With<GlobalRng>>) {
+    commands.insert_resource(Spin {
+        symbols: std::array::from_fn(|_| rng.next_u32() as usize % SYMBOLS.len()),
+        timer: Timer::from_seconds(REEL_STOP_SECS[REEL_COUNT - 1], TimerMode::Once),
+    });
+}
+
+fn spin_reels(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spin: ResMut<Spin>,
+    round: Res<CasinoRound>,
+    mut reels: Query<(&mut ImageNode, &Reel)>,
+    mut payout_messages: MessageWriter<CasinoPayoutMessage>,
+) {
+    spin.timer.tick(time.delta());
+    let elapsed = spin.timer.elapsed_secs();
+    for (mut image, reel) in &mut reels {
+        let symbol = if elapsed >= REEL_STOP_SECS[reel.0] {
+            spin.symbols[reel.0]
+        } else {
+            ((elapsed / SYMBOL_SECS) as usize + reel.0 * 2) % SYMBOLS.len()
+        };
+        if let Some(atlas) = &mut image.texture_atlas {
+            atlas.index = SYMBOLS[symbol].0;
+        }
+    }
+    if !spin.timer.is_finished() {
+        return;
+    }
+
+    let [a, b, c] = spin.symbols;
+    let payout = if a == b && b == c {
+        round.stake * SYMBOLS[a].2
+    } else if a == b || b == c || a == c {
+        round.stake
+    } else {
+        0
+    };
+    payout_messages.write(CasinoPayoutMessage {
+        stake: round.stake,
+        payout,
+        outcome: format!("{} {} {}", SYMBOLS[a].1, SYMBOLS[b].1, SYMBOLS[c].1),
+    });
+    commands.remove_resource::<Spin>();
+}
+
+fn stop_spinning(mut commands: Commands) {
+    commands.remove_resource::<Spin>();
+}
@@ -0,0 +1,306 @@
+use bevy::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState,
+    assets::Assets,
+    economy::Score,
+    gamba::{Bet, BetResultMessage},
+    replay::live_input,
+    rules::Settlement,
+    util::despawn_all,
+};
+
+pub struct CreditPlugin;
+
+impl Plugin for CreditPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<CreditTerms>::new(&["credit.ron"]))
+            .init_resource::<Credit>()
+            .add_message::<CreditMessage>()
+            .add_systems(OnEnter(GameState::Gamba), setup)
+            .add_systems(
+                Update,
+                (
+                    handle_credit_buttons.run_if(live_input),
+                    handle_credit_messages,
+                    settle_credit,
+                    update_credit_panel,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Gamba).or(in_state(GameState::Casino))),
+            )
+            .add_systems(OnExit(GameState::Gamba), despawn_all::<OnGambaScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnGambaScreen;
+
+const CREDIT_SCORE_MAX: u32 = 100;
+/// Money that has to be paid back to earn a credit score point.
+const REPAID_PER_POINT: u64 = 10;
+
+/// Loan conditions of the bank.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct CreditTerms {
+    /// Hand out a free $5–$9 on bankruptcy instead of offering a loan.
+    pub free_bailout: bool,
+    /// Share of the debt added as interest after every round.
+    interest_per_round: f32,
+    /// Credit limit at a credit score of 0.
+    base_limit: u64,
+    /// Extra credit limit per credit score point.
+    limit_per_point: u64,
+    /// Share of every win that goes towards the debt.
+    auto_repay_share: f32,
+    /// Loan amounts offered when the player goes broke.
+    pub loan_offers: Vec<u64>,
+}
+
+/// The player's debt and standing with the bank.
+#[derive(Resource, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Credit {
+    pub debt: u64,
+    /// From 0 to [`CREDIT_SCORE_MAX`], raised by paying back and lowered by going broke in debt.
+    pub credit_score: u32,
+    /// Money paid back that hasn't earned a credit score point yet.
+    repaid: u64,
+}
+
+impl Default for Credit {
+    fn default() -> Self {
+        Self {
+            debt: 0,
+            credit_score: CREDIT_SCORE_MAX / 2,
+            repaid: 0,
+        }
+    }
+}
+
+impl Credit {
+    pub fn limit(&self, terms: &CreditTerms) -> u64 {
+        terms.base_limit + self.credit_score as u64 * terms.limit_per_point
+    }
+
+    /// How much more can be borrowed right now.
+    pub fn available(&self, terms: &CreditTerms) -> u64 {
+        self.limit(terms).saturating_sub(self.debt)
+    }
+
+    fn adjust_credit_score(&mut self, change: i32) {
+        self.credit_score =
+            (self.credit_score as i32 + change).clamp(0, CREDIT_SCORE_MAX as i32) as u32;
+    }
+
+    /// Pays back up to `amount` out of `money`, earning a credit score point for every
+    /// [`REPAID_PER_POINT`] paid back.
+    fn repay(&mut self, money: &mut u64, amount: u64) {
+        let amount = amount.min(self.debt).min(*money);
+        if amount == 0 {
+            return;
+        }
+        *money -= amount;
+        self.debt -= amount;
+        self.repaid += amount;
+        let points = self.repaid / REPAID_PER_POINT;
+        self.repaid %= REPAID_PER_POINT;
+        self.adjust_credit_score(points.min(CREDIT_SCORE_MAX as u64) as i32);
+    }
+}
+
+#[derive(Message, Clone, Serialize, Deserialize)]
+pub enum CreditMessage {
+    Borrow(u64),
+    /// Pays back as much as possible while keeping $1 to bet with.
+    Repay,
+}
+
+#[derive(Component)]
+enum CreditButton {
+    Borrow(u64),
+    Repay,
+}
+
+#[derive(Component)]
+struct CreditDisplay;
+
+fn setup(
+    mut commands: Commands,
+    assets: Res<Assets>,
+    credit_terms: Res<bevy::asset::Assets<CreditTerms>>,
+) {
+    let borrow = credit_terms
+        .get(&assets.credit_terms)
+        .and_then(|terms| terms.loan_offers.first().copied())
+        .unwrap_or(10);
+
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.),
+            padding: UiRect::all(Val::Px(8.)),
+            width: Val::Px(220.),
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(70.),
+            right: Val::Px(0.),
+            ..default()
+        },
+        BackgroundColor(Color::srgba_u8(29, 29, 29, 160)),
+        OnGambaScreen,
+        children![
+            (
+                Text::default(),
+                TextFont {
+                    font_size: 12.,
+                    ..default()
+                },
+                CreditDisplay,
+            ),
+            (
+                Node {
+                    column_gap: Val::Px(4.),
+                    ..default()
+                },
+                children![
+                    (
+                        Button,
+                        CreditButton::Borrow(borrow),
+                        Node {
+                            padding: UiRect::all(Val::Px(4.)),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb_u8(69, 69, 69)),
+                        children![(
+                            Text::new(format!("borrow ${borrow}")),
+                            TextFont {
+                                font_size: 12.,
+                                ..default()
+                            },
+                        )],
+                    ),
+                    (
+                        Button,
+                        CreditButton::Repay,
+                        Node {
+                            padding: UiRect::all(Val::Px(4.)),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb_u8(69, 69, 69)),
+                        children![(
+                            Text::new("repay"),
+                            TextFont {
+                                font_size: 12.,
+                                ..default()
+                            },
+                        )],
+                    ),
+                ],
+            ),
+        ],
+    ));
+}
+
+fn handle_credit_buttons(
+    interaction_query: Query<(&Interaction, &CreditButton), Changed<Interaction>>,
+    mut credit_messages: MessageWriter<CreditMessage>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction == Interaction::Pressed {
+            credit_messages.write(match button {
+                CreditButton::Borrow(amount) => CreditMessage::Borrow(*amount),
+                CreditButton::Repay => CreditMessage::Repay,
+            });
+        }
+    }
+}
+
+fn handle_credit_messages(
+    mut credit_messages: MessageReader<CreditMessage>,
+    mut credit: ResMut<Credit>,
+    mut score: ResMut<Score>,
+    mut bet: ResMut<Bet>,
+    assets: Res<Assets>,
+    credit_terms: Res<bevy::asset::Assets<CreditTerms>>,
+) {
+    let Some(terms) = credit_terms.get(&assets.credit_terms) else {
+        return;
+    };
+    for msg in credit_messages.read() {
+        match msg {
+            CreditMessage::Borrow(amount) => {
+                let amount = (*amount).min(credit.available(terms));
+                if amount == 0 {
+                    continue;
+                }
+                score.0 += amount;
+                credit.debt += amount;
+                bet.0 = bet.0.clamp(1, score.0);
+            }
+            CreditMessage::Repay => {
+                let amount = score.0.saturating_sub(1);
+                credit.repay(&mut score.0, amount);
+            }
+        }
+    }
+}
+
+/// Pays part of every win towards the debt and charges interest after every round.
+fn settle_credit(
+    mut result_messages: MessageReader<BetResultMessage>,
+    mut credit: ResMut<Credit>,
+    mut score: ResMut<Score>,
+    assets: Res<Assets>,
+    credit_terms: Res<bevy::asset::Assets<CreditTerms>>,
+) {
+    let Some(terms) = credit_terms.get(&assets.credit_terms) else {
+        return;
+    };
+    for result in result_messages.read() {
+        if credit.debt == 0 {
+            continue;
+        }
+        match result.settlement {
+            Settlement::Won => {
+                let winnings = result.score_after - result.score_before;
+                let amount = (winnings as f32 * terms.auto_repay_share).ceil() as u64;
+                credit.repay(&mut score.0, amount);
+            }
+            Settlement::Lost => (),
+            Settlement::Bankrupt => credit.adjust_credit_score(-10),
+        }
+        if credit.debt > 0 {
+            credit.debt += (credit.debt as f32 * terms.interest_per_round).ceil() as u64;
+            if credit.debt > credit.limit(terms) {
+                credit.adjust_credit_score(-2);
+            }
+        }
+    }
+}
+
+fn update_credit_panel(
+    credit: Res<Credit>,
+    assets: Res<Assets>,
+    credit_terms: Res<bevy::asset::Assets<CreditTerms>>,
+    mut display: Query<&mut Text, With<CreditDisplay>>,
+    spawned: Query<(), Added<CreditDisplay>>,
+) {
+    if !credit.is_changed() && spawned.is_empty() {
+        return;
+    }
+    let Some(terms) = credit_terms.get(&assets.credit_terms) else {
+        return;
+    };
+    for mut text in &mut display {
+        **text = format!(
+            "debt: ${} (+{:.0}% per round)\ncredit score: {}/{}\ncredit limit: ${}",
+            credit.debt,
+            terms.interest_per_round * 100.,
+            credit.credit_score,
+            CREDIT_SCORE_MAX,
+            credit.limit(terms),
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::BTreeSet;
+
+use bevy::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState, assets::Assets, gamba::SugarCaneHeight, replay::live_input, util::despawn_all,
+};
+
+/// Money and sugar shared by the cake and the gamba: finished cakes pay out, and canes won in the
+/// gamba unlock fancier cake recipes.
+///
+/// Both [`crate::cake::CakePlugin`] and [`crate::gamba::GambaPlugin`] add it if it isn't there yet.
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<RecipeBook>::new(&["recipes.ron"]))
+            .insert_resource(Score(1))
+            .init_resource::<Pantry>()
+            .add_message::<EconomyMessage>()
+            .add_message::<CakeFinishedMessage>()
+            .add_message::<NewCakeMessage>()
+            .add_systems(OnEnter(GameState::Cake), setup)
+            .add_systems(
+                Update,
+                (
+                    handle_bakery_buttons.run_if(live_input),
+                    handle_economy_messages,
+                    pay_for_cakes,
+                    update_bakery_panel,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Cake)),
+            )
+            .add_systems(OnExit(GameState::Cake), despawn_all::<OnCakeScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnCakeScreen;
+
+#[derive(Resource)]
+pub struct Score(pub u64);
+
+/// Every cake that can be baked, in the order they are offered.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct RecipeBook {
+    recipes: Vec<Recipe>,
+}
+
+#[derive(Deserialize)]
+pub struct Recipe {
+    name: String,
+    /// Sugar canes it takes to unlock, 0 for recipes known from the start.
+    unlock: u64,
+    /// Colour the cake is tinted with.
+    tint: (u8, u8, u8),
+    /// Paid out every time one is eaten up.
+    reward: Reward,
+}
+
+impl Recipe {
+    pub fn tint(&self) -> Color {
+        let (r, g, b) = self.tint;
+        Color::srgb_u8(r, g, b)
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+enum Reward {
+    Dollars(u64),
+    /// Sugar canes, planted on the gamba's shortest stack.
+    Sugar(u64),
+}
+
+impl Reward {
+    fn label(&self) -> String {
+        match self {
+            Reward::Dollars(amount) => format!("${amount}"),
+            Reward::Sugar(amount) => format!("{amount} canes"),
+        }
+    }
+}
+
+/// Recipes unlocked so far and the one being baked.
+#[derive(Resource, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Pantry {
+    unlocked: BTreeSet<String>,
+    /// `None` for the first recipe in the book.
+    recipe: Option<String>,
+}
+
+impl Pantry {
+    fn knows(&self, recipe: &Recipe) -> bool {
+        recipe.unlock == 0 || self.unlocked.contains(&recipe.name)
+    }
+}
+
+impl RecipeBook {
+    /// The recipe the pantry is set to, falling back to the first one.
+    pub fn current(&self, pantry: &Pantry) -> Option<&Recipe> {
+        pantry
+            .recipe
+            .as_ref()
+            .and_then(|name| self.recipes.iter().find(|recipe| recipe.name == *name))
+            .or(self.recipes.first())
+    }
+}
+
+#[derive(Message, Clone, Serialize, Deserialize)]
+pub enum EconomyMessage {
+    /// Spends sugar canes on a recipe and starts baking it.
+    Unlock(String),
+    /// Switches to an unlocked recipe.
+    Bake(String),
+}
+
+/// Sent by the cake once the last bite of a whole cake is taken.
+#[derive(Message)]
+pub struct CakeFinishedMessage;
+
+/// Tells the cake to start over with the current recipe.
+#[derive(Message)]
+pub struct NewCakeMessage;
+
+#[derive(Component)]
+struct RecipeButton(usize);
+
+#[derive(Component)]
+struct RecipeLabel(usize);
+
+fn setup(
+    mut commands: Commands,
+    assets: Res<Assets>,
+    recipe_books: Res<bevy::asset::Assets<RecipeBook>>,
+) {
+    let Some(book) = recipe_books.get(&assets.recipes) else {
+        return;
+    };
+    commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.),
+                padding: UiRect::all(Val::Px(8.)),
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(0.),
+                left: Val::Px(0.),
+                ..default()
+            },
+            BackgroundColor(Color::srgba_u8(69, 69, 69, 160)),
+            OnCakeScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("bakery"),
+                TextFont {
+                    font_size: 16.,
+                    ..default()
+                },
+            ));
+            for (i, recipe) in book.recipes.iter().enumerate() {
+                parent.spawn((
+                    Button,
+                    RecipeButton(i),
+                    Node {
+                        padding: UiRect::all(Val::Px(4.)),
+                        ..default()
+                    },
+                    BackgroundColor(recipe.tint().darker(0.4)),
+                    children![(
+                        Text::default(),
+                        TextFont {
+                            font_size: 12.,
+                            ..default()
+                        },
+                        RecipeLabel(i),
+                    )],
+                ));
+            }
+        });
+}
+
+fn handle_bakery_buttons(
+    interaction_query: Query<(&Interaction, &RecipeButton), Changed<Interaction>>,
+    pantry: Res<Pantry>,
+    assets: Res<Assets>,
+    recipe_books: Res<bevy::asset::Assets<RecipeBook>>,
+    mut economy_messages: MessageWriter<EconomyMessage>,
+) {
+    let Some(book) = recipe_books.get(&assets.recipes) else {
+        return;
+    };
+    for (interaction, button) in interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(recipe) = book.recipes.get(button.0) else {
+            continue;
+        };
+        economy_messages.write(if pantry.knows(recipe) {
+            EconomyMessage::Bake(recipe.name.clone())
+        } else {
+            EconomyMessage::Unlock(recipe.name.clone())
+        });
+    }
+}
+
+fn handle_economy_messages(
+    mut economy_messages: MessageReader<EconomyMessage>,
+    mut pantry: ResMut<Pantry>,
+    mut sugar_cane_height: ResMut<SugarCaneHeight>,
+    assets: Res<Assets>,
+    recipe_books: Res<bevy::asset::Assets<RecipeBook>>,
+    mut new_cake_messages: MessageWriter<NewCakeMessage>,
+) {
+    let Some(book) = recipe_books.get(&assets.recipes) else {
+        return;
+    };
+    for msg in economy_messages.read() {
+        let (EconomyMessage::Unlock(name) | EconomyMessage::Bake(name)) = msg;
+        let Some(recipe) = book.recipes.iter().find(|recipe| recipe.name == *name) else {
+            continue;
+        };
+        if !pantry.knows(recipe) {
+            if !matches!(msg, EconomyMessage::Unlock(_))
+                || sugar_cane_height.total() < recipe.unlock
+            {
+                continue;
+            }
+            for _ in 0..recipe.unlock {
+                sugar_cane_height.cut();
+            }
+            pantry.unlocked.insert(recipe.name.clone());
+        }
+        if book
+            .current(&pantry)
+            .is_some_and(|current| current.name != *name)
+        {
+            pantry.recipe = Some(name.clone());
+            new_cake_messages.write(NewCakeMessage);
+        }
+    }
+}
+
+fn pay_for_cakes(
+    mut finished_messages: MessageReader<CakeFinishedMessage>,
+    pantry: Res<Pantry>,
+    mut score: ResMut<Score>,
+    mut sugar_cane_height: ResMut<SugarCaneHeight>,
+    assets: Res<Assets>,
+    recipe_books: Res<bevy::asset::Assets<RecipeBook>>,
+) {
+    let Some(recipe) = recipe_books
+        .get(&assets.recipes)
+        .and_then(|book| book.current(&pantry))
+    else {
+        return;
+    };
+    for _ in finished_messages.read() {
+        match recipe.reward {
+            Reward::Dollars(amount) => score.0 = score.0.saturating_add(amount),
+            Reward::Sugar(amount) => {
+                for _ in 0..amount {
+                    sugar_cane_height.plant();
+                }
+            }
+        }
+    }
+}
+
+fn update_bakery_panel(
+    pantry: Res<Pantry>,
+    sugar_cane_height: Res<SugarCaneHeight>,
+    assets: Res<Assets>,
+    recipe_books: Res<bevy::asset::Assets<RecipeBook>>,
+    mut labels: Query<(&mut Text, &RecipeLabel)>,
+    spawned: Query<(), Added<RecipeLabel>>,
+) {
+    if !pantry.is_changed() && !sugar_cane_height.is_changed() && spawned.is_empty() {
+        return;
+    }
+    let Some(book) = recipe_books.get(&assets.recipes) else {
+        return;
+    };
+    let current = book.current(&pantry).map(|recipe| recipe.name.as_str());
+    for (mut text, label) in &mut labels {
+        let Some(recipe) = book.recipes.get(label.0) else {
+            continue;
+        };
+        **text = if current == Some(recipe.name.as_str()) {
+            format!("{} (baking, pays {})", recipe.name, recipe.reward.label())
+        } else if pantry.knows(recipe) {
+            format!("bake {} (pays {})", recipe.name, recipe.reward.label())
+        } else {
+            format!(
+                "unlock {} for {} canes ({} grown)",
+                recipe.name,
+                recipe.unlock,
+                sugar_cane_height.total()
+            )
+        };
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    input::keyboard::{Key, KeyboardInput},
+    prelude::*,
+};
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, TryRngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    GameState,
+    replay::live_input,
+    rules::{Outcome, side_name},
+    util::despawn_all,
+};
+
+pub struct FairPlugin;
+
+impl Plugin for FairPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProvablyFair>()
+            .add_message::<FairMessage>()
+            .add_systems(OnEnter(GameState::Gamba), setup)
+            .add_systems(
+                Update,
+                (
+                    (handle_fair_buttons, edit_client_seed).run_if(live_input),
+                    handle_fair_messages,
+                    update_fair_panel,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Gamba)),
+            )
+            .add_systems(OnExit(GameState::Gamba), despawn_all::<OnGambaScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnGambaScreen;
+
+/// How many revealed server seeds are kept around for the verifier.
+const REVEALED_SEEDS: usize = 5;
+/// How many rounds of a revealed seed the verifier lists.
+const VERIFIED_ROUNDS: usize = 8;
+
+/// Commit/reveal state for deriving coin flips from a hashed server seed, the player's client
+/// seed and a per-round nonce.
+///
+/// Server seeds are drawn from OS entropy rather than the global RNG, which anyone with the
+/// session seed could reproduce. Replays hand out the seeds of their recording instead.
+#[derive(Resource)]
+pub struct ProvablyFair {
+    pub enabled: bool,
+    server_seed: Option<ServerSeed>,
+    /// Every server seed drawn this session, in order.
+    drawn_seeds: Vec<[u8; 32]>,
+    /// Seeds left to hand out while replaying.
+    replay_seeds: Option<VecDeque<[u8; 32]>>,
+    client_seed: String,
+    nonce: u64,
+    rounds: Vec<FairRound>,
+    revealed: Vec<RevealedSeed>,
+    editing_client_seed: Option<String>,
+}
+
+impl Default for ProvablyFair {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            server_seed: None,
+            drawn_seeds: Vec::new(),
+            replay_seeds: None,
+            client_seed: "mew".into(),
+            nonce: 0,
+            rounds: Vec::new(),
+            revealed: Vec::new(),
+            editing_client_seed: None,
+        }
+    }
+}
+
+#[derive(Message, Clone, Serialize, Deserialize)]
+pub enum FairMessage {
+    Toggle,
+    SetClientSeed(String),
+    Rotate,
+}
+
+struct ServerSeed {
+    seed: [u8; 32],
+    /// The hash shown to the player while the seed was in use.
+    commitment: String,
+}
+
+struct FairRound {
+    client_seed: String,
+    nonce: u64,
+    outcome: Outcome,
+}
+
+struct RevealedSeed {
+    server_seed: [u8; 32],
+    commitment: String,
+    rounds: Vec<FairRound>,
+}
+
+impl ProvablyFair {
+    /// Reveals the current server seed and commits to a fresh one.
+    pub fn rotate(&mut self) {
+        if let Some(server_seed) = self.server_seed.take() {
+            self.revealed.push(RevealedSeed {
+                server_seed: server_seed.seed,
+                commitment: server_seed.commitment,
+                rounds: std::mem::take(&mut self.rounds),
+            });
+            if self.revealed.len() > REVEALED_SEEDS {
+                self.revealed.remove(0);
+            }
+        }
+        let seed = self.draw_seed();
+        self.server_seed = Some(ServerSeed {
+            seed,
+            commitment: commitment(&seed),
+        });
+        self.nonce = 0;
+    }
+
+    fn draw_seed(&mut self) -> [u8; 32] {
+        let seed = match self.replay_seeds.as_mut().and_then(VecDeque::pop_front) {
+            Some(seed) => seed,
+            None => {
+                let mut seed = [0; 32];
+                OsRng
+                    .try_fill_bytes(&mut seed)
+                    .expect("the OS provides entropy");
+                seed
+            }
+        };
+        self.drawn_seeds.push(seed);
+        seed
+    }
+
+    /// Every server seed drawn this session, for recordings.
+    pub fn drawn_seeds(&self) -> &[[u8; 32]] {
+        &self.drawn_seeds
+    }
+
+    /// Hands out `seeds` in order instead of drawing new ones, to play a recording back.
+    pub fn replay_seeds(&mut self, seeds: Vec<[u8; 32]>) {
+        self.replay_seeds = Some(seeds.into());
+    }
+
+    /// Derives the outcome of the next round and records it for later verification.
+    pub fn next_outcome(&mut self) -> Outcome {
+        if self.server_seed.is_none() {
+            self.rotate();
+        }
+        let server_seed = self
+            .server_seed
+            .as_ref()
+            .map(|server_seed| server_seed.seed)
+            .unwrap_or_default();
+        let outcome = derive_outcome(&server_seed, &self.client_seed, self.nonce);
+        self.rounds.push(FairRound {
+            client_seed: self.client_seed.clone(),
+            nonce: self.nonce,
+            outcome: outcome.clone(),
+        });
+        self.nonce += 1;
+        outcome
+    }
+}
+
+/// The published hash of a server seed.
+pub fn commitment(server_seed: &[u8; 32]) -> String {
+    hex(&Sha256::digest(server_seed))
+}
+
+/// Rolls HMAC-SHA256 of `client_seed:nonce` keyed with the server seed.
+pub fn roll(server_seed: &[u8; 32], client_seed: &str, nonce: u64) -> u32 {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(server_seed).expect("HMAC accepts keys of any size");
+    mac.update(format!("{client_seed}:{nonce}").as_bytes());
+    let bytes = mac.finalize().into_bytes();
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+pub fn derive_outcome(server_seed: &[u8; 32], client_seed: &str, nonce: u64) -> Outcome {
+    Outcome::from_roll(roll(server_seed, client_seed, nonce))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Recomputes every round of a revealed seed and describes whether it matches.
+fn verify(revealed: &RevealedSeed) -> String {
+    let commitment_ok = commitment(&revealed.server_seed) == revealed.commitment;
+    let mut report = format!(
+        "seed {}\nhash {}\n",
+        hex(&revealed.server_seed),
+        if commitment_ok { "ok" } else { "MISMATCH" },
+    );
+    let skip = revealed.rounds.len().saturating_sub(VERIFIED_ROUNDS);
+    for round in revealed.rounds.iter().skip(skip) {
+        let outcome = derive_outcome(&revealed.server_seed, &round.client_seed, round.nonce);
+        report += &format!(
+            "#{} {}: {} {} {}\n",
+            round.nonce,
+            round.client_seed,
+            side_name(&round.outcome.side),
+            round.outcome.column + 1,
+            if outcome == round.outcome {
+                "ok"
+            } else {
+                "MISMATCH"
+            },
+        );
+    }
+    report
+}
+
+#[derive(Component)]
+enum FairButton {
+    Toggle,
+    EditClientSeed,
+    Rotate,
+}
+
+#[derive(Component)]
+struct FairModeDisplay;
+
+#[derive(Component)]
+struct CommitmentDisplay;
+
+#[derive(Component)]
+struct ClientSeedDisplay;
+
+#[derive(Component)]
+struct NonceDisplay;
+
+#[derive(Component)]
+struct VerifierDisplay;
+
+fn setup(mut commands: Commands, mut fair: ResMut<ProvablyFair>) {
+    if fair.server_seed.is_none() {
+        fair.rotate();
+    }
+
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.),
+            padding: UiRect::all(Val::Px(8.)),
+            width: Val::Px(320.),
+            position_type: PositionType::Absolute,
+            top: Val::Px(0.),
+            left: Val::Px(0.),
+            ..default()
+        },
+        BackgroundColor(Color::srgba_u8(29, 29, 29, 160)),
+        OnGambaScreen,
+        children![
+            (
+                Button,
+                FairButton::Toggle,
+                Node {
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb_u8(69, 69, 69)),
+                children![(
+                    Text::new("provably fair: "),
+                    TextFont {
+                        font_size: 16.,
+                        ..default()
+                    },
+                    children![(TextSpan::default(), FairModeDisplay)],
+                )],
+            ),
+            (
+                Text::new("server seed hash: "),
+                TextFont {
+                    font_size: 10.,
+                    ..default()
+                },
+                children![(TextSpan::default(), CommitmentDisplay)],
+            ),
+            (
+                Button,
+                FairButton::EditClientSeed,
+                Node {
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb_u8(69, 69, 69)),
+                children![(
+                    Text::new("client seed: "),
+                    TextFont {
+                        font_size: 12.,
+                        ..default()
+                    },
+                    children![(TextSpan::default(), ClientSeedDisplay)],
+                )],
+            ),
+            (
+                Text::new("nonce: "),
+                TextFont {
+                    font_size: 12.,
+                    ..default()
+                },
+                children![(TextSpan::default(), NonceDisplay)],
+            ),
+            (
+                Button,
+                FairButton::Rotate,
+                Node {
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb_u8(69, 69, 69)),
+                children![(
+                    Text::new("reveal & rotate seed"),
+                    TextFont {
+                        font_size: 12.,
+                        ..default()
+                    },
+                )],
+            ),
+            (
+                Text::default(),
+                TextFont {
+                    font_size: 10.,
+                    ..default()
+                },
+                VerifierDisplay,
+            ),
+        ],
+    ));
+}
+
+fn handle_fair_buttons(
+    interaction_query: Query<(&Interaction, &FairButton), Changed<Interaction>>,
+    mut fair: ResMut<ProvablyFair>,
+    mut fair_messages: MessageWriter<FairMessage>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction == Interaction::Pressed {
+            match button {
+                FairButton::Toggle => {
+                    fair_messages.write(FairMessage::Toggle);
+                }
+                FairButton::EditClientSeed => {
+                    fair.editing_client_seed = Some(String::new());
+                }
+                FairButton::Rotate => {
+                    fair_messages.write(FairMessage::Rotate);
+                }
+            }
+        }
+    }
+}
+
+fn edit_client_seed(
+    mut keyboard_messages: MessageReader<KeyboardInput>,
+    mut fair: ResMut<ProvablyFair>,
+    mut fair_messages: MessageWriter<FairMessage>,
+) {
+    for msg in keyboard_messages.read() {
+        if !msg.state.is_pressed() {
+            continue;
+        }
+        let Some(seed) = &mut fair.editing_client_seed else {
+            continue;
+        };
+        match &msg.logical_key {
+            Key::Character(c) => seed.push_str(c),
+            Key::Space => seed.push(' '),
+            Key::Backspace => {
+                seed.pop();
+            }
+            Key::Escape => fair.editing_client_seed = None,
+            Key::Enter => {
+                if let Some(seed) = fair.editing_client_seed.take()
+                    && !seed.is_empty()
+                {
+                    fair_messages.write(FairMessage::SetClientSeed(seed));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn handle_fair_messages(
+    mut fair_messages: MessageReader<FairMessage>,
+    mut fair: ResMut<ProvablyFair>,
+) {
+    for msg in fair_messages.read() {
+        match msg {
+            FairMessage::Toggle => fair.enabled = !fair.enabled,
+            FairMessage::SetClientSeed(seed) => {
+                // A new client seed starts a new series, so the old server seed is revealed.
+                fair.client_seed = seed.clone();
+                fair.rotate();
+            }
+            FairMessage::Rotate => fair.rotate(),
+        }
+    }
+}
+
+fn update_fair_panel(
+    fair: Res<ProvablyFair>,
+    mut spans: Query<(
+        &mut TextSpan,
+        Has<FairModeDisplay>,
+        Has<CommitmentDisplay>,
+        Has<ClientSeedDisplay>,
+        Has<NonceDisplay>,
+    )>,
+    mut verifier: Query<&mut Text, With<VerifierDisplay>>,
+    spawned: Query<(), Added<VerifierDisplay>>,
+) {
+    if !fair.is_changed() && spawned.is_empty() {
+        return;
+    }
+    for (mut span, mode, commitment_display, client_seed, nonce) in &mut spans {
+        if mode {
+            **span = if fair.enabled { "on" } else { "off" }.into();
+        } else if commitment_display {
+            **span = fair
+                .server_seed
+                .as_ref()
+                .map(|server_seed| server_seed.commitment.clone())
+                .unwrap_or_default();
+        } else if client_seed {
+            **span = match &fair.editing_client_seed {
+                Some(seed) => format!("{seed}_"),
+                None => fair.client_seed.clone(),
+            };
+        } else if nonce {
+            **span = fair.nonce.to_string();
+        }
+    }
+    for mut text in &mut verifier {
+        **text = fair.revealed.last().map(verify).unwrap_or_default();
+    }
+}
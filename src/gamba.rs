@@ -1,104 +1,331 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_common_assets::ron::RonAssetPlugin;
 use bevy_light_2d::prelude::*;
 use bevy_rand::prelude::*;
 use rand::seq::IteratorRandom;
 use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     GameState,
+    animation::AnimationClips,
     assets::Assets,
-    util::{Animation, animate, despawn_all},
+    credit::{Credit, CreditMessage, CreditTerms},
+    economy::{EconomyPlugin, Score},
+    fair::ProvablyFair,
+    particles::{ParticleEmitter, ParticlePlugin},
+    replay::{Replay, live_input},
+    rules::{
+        BetKind, OddsTable, Outcome, Parity, SUGAR_CANE_COUNT, Settlement, Side, bailout, settle,
+        side_name,
+    },
+    safeguards::{SafeguardSettings, Session},
+    save::Stats,
+    tween::{Tween, TweenCompleted, TweenTarget},
+    util::despawn_all,
 };
 
+pub mod fishing;
+pub mod income;
+pub mod market;
+
 pub struct GambaPlugin;
 
 impl Plugin for GambaPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Score(1))
+        if !app.is_plugin_added::<EconomyPlugin>() {
+            app.add_plugins(EconomyPlugin);
+        }
+        if !app.is_plugin_added::<ParticlePlugin>() {
+            app.add_plugins(ParticlePlugin);
+        }
+        app.add_plugins(RonAssetPlugin::<OddsTable>::new(&["odds.ron"]))
+            .add_plugins((
+                fishing::FishingPlugin,
+                income::IncomePlugin,
+                market::MarketPlugin,
+            ))
+            .init_resource::<SugarCaneHeight>()
             .insert_resource(Bet(1))
             .add_message::<BetChangeMessage>()
             .add_message::<BetMessage>()
+            .add_message::<BetResultMessage>()
+            .add_message::<BetRejectedMessage>()
+            .add_sub_state::<GambaRoundState>()
             .add_systems(OnEnter(GameState::Gamba), setup)
             .add_systems(
                 Update,
                 (
-                    handle_bet_messages,
-                    handle_bet_change_messages,
-                    despawn_bankruptcy_messages,
-                    move_camera,
-                    animate::<WaterSurfaceAnimation>,
-                    animate::<UnderwaterAnimation>,
+                    handle_bet_offers.run_if(live_input.and(betting_open)),
+                    place_bet.run_if(in_state(GambaRoundState::Betting)),
+                    finish_reveal.run_if(in_state(GambaRoundState::Revealing)),
+                    finish_payout.run_if(in_state(GambaRoundState::Payout)),
                 )
                     .run_if(in_state(GameState::Gamba)),
             )
-            .add_systems(OnExit(GameState::Gamba), despawn_all::<OnGambaScreen>);
+            .add_systems(
+                Update,
+                (
+                    handle_bet_change_messages.before(place_bet),
+                    handle_loan_offers.run_if(live_input),
+                    despawn_bankruptcy_messages,
+                )
+                    .run_if(in_state(GameState::Gamba).or(in_state(GameState::Casino))),
+            )
+            .add_systems(OnEnter(GambaRoundState::Payout), pay_out)
+            .add_systems(
+                OnExit(GameState::Gamba),
+                (
+                    despawn_all::<OnGambaScreen>,
+                    despawn_all::<BrokeMessage>,
+                    abandon_round,
+                ),
+            )
+            .add_systems(OnExit(GameState::Casino), despawn_all::<BrokeMessage>);
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 struct OnGambaScreen;
 
+/// Any of the pond's layers, clicking one casts a fishing line.
+#[derive(Component)]
+pub struct Pond;
+
 const SUGAR_CANE_SCALE: Vec3 = Vec3::splat(1.5);
 const SUGAR_CANE_SIZE: f32 = 42.666;
 const SUGAR_CANE_INDEX: usize = 50;
 const SUGAR_CANE_VARIANTS: usize = 4;
-const SUGAR_CANE_COUNT: usize = 6;
 const SIGN_INDEX: usize = 60;
 const POND_SCALE: Vec3 = Vec3::splat(10.);
 const POND_POS: Vec2 = Vec2::new(0., -180.);
 const POND_WATER_INDEX: usize = 0;
 const POND_LILY_INDEX: usize = 2;
+/// How far the canes lean while the outcome is being revealed, in radians.
+const WOBBLE_ANGLE: f32 = 0.12;
+const WOBBLE_SECS: f32 = 0.2;
+const REVEAL_WOBBLES: usize = 3;
+const REVEAL_SECS: f32 = REVEAL_WOBBLES as f32 * WOBBLE_SECS * 2.;
+
+/// Phases of a single gamba round.
+#[derive(SubStates, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[source(GameState = GameState::Gamba)]
+pub enum GambaRoundState {
+    /// Waiting for a bet, the only phase that accepts one.
+    #[default]
+    Betting,
+    /// The canes wobble while the outcome is kept secret.
+    Revealing,
+    /// The bet is settled, the new cane grows and the camera climbs up to it.
+    Payout,
+}
+
+/// Run condition that is true while a new bet can be placed.
+pub fn betting_open(round_state: Option<Res<State<GambaRoundState>>>) -> bool {
+    round_state.is_some_and(|state| *state.get() == GambaRoundState::Betting)
+}
 
+/// The bet of the round in progress and the outcome it will be settled against.
 #[derive(Resource)]
+struct PendingRound {
+    bet: BetKind,
+    stake: u64,
+    outcome: Outcome,
+    reveal: Timer,
+}
+
+/// Canes grown on top of the bottom row, kept between visits as the player's cane holdings.
+#[derive(Resource, Serialize, Deserialize, Default, Clone)]
 pub struct SugarCaneHeight {
     left: [usize; SUGAR_CANE_COUNT],
     right: [usize; SUGAR_CANE_COUNT],
 }
 
-#[derive(Component, Clone, PartialEq)]
-pub enum Side {
-    Left,
-    Right,
+impl SugarCaneHeight {
+    fn get_mut(&mut self, side: &Side, column: usize) -> &mut usize {
+        match side {
+            Side::Left => &mut self.left[column],
+            Side::Right => &mut self.right[column],
+        }
+    }
+
+    /// Every stack, alternating sides outwards from the sign.
+    fn stacks(&self) -> impl Iterator<Item = (Side, usize, usize)> + '_ {
+        (0..SUGAR_CANE_COUNT).flat_map(|column| {
+            [
+                (Side::Left, column, self.left[column]),
+                (Side::Right, column, self.right[column]),
+            ]
+        })
+    }
+
+    pub fn total(&self) -> u64 {
+        self.left.iter().chain(&self.right).sum::<usize>() as u64
+    }
+
+    /// Adds a cane to the shortest stack, returning where it went.
+    pub fn plant(&mut self) -> Option<Outcome> {
+        let (side, column, _) = self.stacks().min_by_key(|(_, _, height)| *height)?;
+        *self.get_mut(&side, column) += 1;
+        Some(Outcome { side, column })
+    }
+
+    /// Takes the top cane off the tallest stack, returning where it was and its row.
+    pub fn cut(&mut self) -> Option<(Outcome, usize)> {
+        let (side, column, height) = self
+            .stacks()
+            .filter(|(_, _, height)| *height > 0)
+            .max_by_key(|(_, _, height)| *height)?;
+        *self.get_mut(&side, column) -= 1;
+        Some((Outcome { side, column }, height))
+    }
 }
 
-#[derive(Resource)]
-pub struct Score(pub u64);
+/// Column of a sugar cane sprite, counted outwards from the sign starting at 0.
+#[derive(Component)]
+pub struct Column(usize);
+
+/// Row of a sugar cane sprite, counted upwards from the bottom row at 0.
+#[derive(Component)]
+pub struct Row(usize);
+
+/// The sign between the cane fields.
+#[derive(Component)]
+pub struct Sign;
+
+/// Plants and cuts canes outside of a round, for trading them on the cane market.
+#[derive(SystemParam)]
+pub struct CaneField<'w, 's> {
+    commands: Commands<'w, 's>,
+    rng: Single<'w, 's, &'static mut WyRand, With<GlobalRng>>,
+    assets: Res<'w, Assets>,
+    heights: ResMut<'w, SugarCaneHeight>,
+    canes: Query<'w, 's, (Entity, &'static Side, &'static Column, &'static Row)>,
+}
+
+impl CaneField<'_, '_> {
+    /// Grows a cane on the shortest stack.
+    pub fn plant(&mut self) {
+        let Some(outcome) = self.heights.plant() else {
+            return;
+        };
+        let height = *self.heights.get_mut(&outcome.side, outcome.column);
+        grow_sugar_cane(
+            &mut self.commands,
+            &mut self.rng,
+            &self.assets,
+            outcome,
+            height,
+        );
+    }
+
+    /// Cuts the top cane off the tallest stack, returning `false` when there is none to cut.
+    pub fn cut(&mut self) -> bool {
+        let Some((outcome, height)) = self.heights.cut() else {
+            return false;
+        };
+        for (entity, side, column, row) in &self.canes {
+            if *side == outcome.side && column.0 == outcome.column && row.0 == height {
+                self.commands.entity(entity).despawn();
+            }
+        }
+        true
+    }
+}
 
 #[derive(Resource)]
 pub struct Bet(pub u64);
 
-#[derive(Message)]
+#[derive(Message, Clone, Serialize, Deserialize)]
 pub struct BetChangeMessage(pub BetChange);
 
+#[derive(Clone, Serialize, Deserialize)]
 pub enum BetChange {
     Increase,
     Decrease,
+    /// Sets the bet outright, clamped to the score.
+    Set(u64),
+}
+
+#[derive(Message, Clone, Serialize, Deserialize)]
+pub struct BetMessage(pub BetKind);
+
+/// Sent after every settled bet, in the gamba and the casino alike.
+#[derive(Message)]
+pub struct BetResultMessage {
+    pub round: SettledRound,
+    pub stake: u64,
+    /// Score before the stake was taken.
+    pub score_before: u64,
+    /// Score after the bet, including any bankruptcy bailout.
+    pub score_after: u64,
+    pub settlement: Settlement,
+}
+
+impl BetResultMessage {
+    /// Money won or lost on the bet itself, leaving out any bailout.
+    pub fn net(&self) -> i64 {
+        match self.settlement {
+            Settlement::Won | Settlement::Lost => {
+                self.score_after as i64 - self.score_before as i64
+            }
+            Settlement::Bankrupt => -(self.stake as i64),
+        }
+    }
+}
+
+/// What a settled bet was on and how it turned out.
+#[derive(Clone)]
+pub enum SettledRound {
+    Gamba {
+        bet: BetKind,
+        outcome: Outcome,
+    },
+    Casino {
+        /// [`CasinoGame::NAME`](crate::casino::CasinoGame::NAME) of the game.
+        game: &'static str,
+        choice: String,
+        outcome: String,
+    },
 }
 
+impl SettledRound {
+    pub fn bet_label(&self) -> String {
+        match self {
+            SettledRound::Gamba { bet, .. } => bet.label(),
+            SettledRound::Casino { game, choice, .. } => format!("{game}: {choice}"),
+        }
+    }
+
+    pub fn outcome_label(&self) -> String {
+        match self {
+            SettledRound::Gamba { outcome, .. } => {
+                format!("{} {}", side_name(&outcome.side), outcome.column + 1)
+            }
+            SettledRound::Casino { outcome, .. } => outcome.clone(),
+        }
+    }
+}
+
+/// Sent instead of a [`BetResultMessage`] when a bet is refused before the round starts.
 #[derive(Message)]
-pub struct BetMessage(pub Side);
+pub struct BetRejectedMessage {
+    pub reason: String,
+}
 
 fn setup(
     mut commands: Commands,
     mut rng: Single<&mut WyRand, With<GlobalRng>>,
     assets: Res<Assets>,
     mut light_query: Query<&mut Light2d, With<Camera>>,
+    clips: AnimationClips,
+    sugar_cane_height: Res<SugarCaneHeight>,
 ) {
     commands.insert_resource(ClearColor(Color::srgb_u8(63, 127, 255)));
-    commands.insert_resource(CameraMoveState {
-        start: 0.0,
-        target: 0.0,
-        timer: Timer::default(),
-    });
     for mut light in &mut light_query {
         light.ambient_light.brightness = 1.;
     }
 
-    commands.insert_resource(SugarCaneHeight {
-        left: [0, 0, 0, 0, 0, 0],
-        right: [0, 0, 0, 0, 0, 0],
-    });
-
     // Grass
     commands.spawn((
         Sprite {
@@ -115,75 +342,57 @@ fn setup(
             assets.textures.clone(),
             TextureAtlas::from(assets.texture_atlas.clone()).with_index(SIGN_INDEX),
         ),
+        Sign,
         OnGambaScreen,
         Transform::from_scale(SUGAR_CANE_SCALE),
     ));
 
-    // Pond water
-    commands.spawn((
-        Sprite::from_atlas_image(
-            assets.pond.clone(),
-            TextureAtlas::from(assets.pond_atlas.clone()).with_index(POND_WATER_INDEX),
-        ),
-        OnGambaScreen,
-        Transform::from_translation(POND_POS.extend(1.)).with_scale(POND_SCALE),
-    ));
-
-    // Pond lilies
-    commands.spawn((
-        Sprite::from_atlas_image(
-            assets.pond.clone(),
-            TextureAtlas::from(assets.pond_atlas.clone()).with_index(POND_LILY_INDEX),
-        ),
-        OnGambaScreen,
-        Transform::from_translation(POND_POS.extend(4.)).with_scale(POND_SCALE),
-    ));
-
-    // Pond water surface
-    commands.spawn((
-        Sprite::from_atlas_image(
-            assets.pond.clone(),
-            TextureAtlas::from(assets.pond_atlas.clone())
-                .with_index(WaterSurfaceAnimation::index()),
-        ),
-        OnGambaScreen,
-        Transform::from_translation(POND_POS.extend(3.)).with_scale(POND_SCALE),
-        WaterSurfaceAnimation(Timer::from_seconds(2., TimerMode::Repeating)),
-    ));
-
-    // Pond underwater
-    commands.spawn((
-        Sprite::from_atlas_image(
-            assets.pond.clone(),
-            TextureAtlas::from(assets.pond_atlas.clone()).with_index(UnderwaterAnimation::index()),
-        ),
-        OnGambaScreen,
-        Transform::from_translation(POND_POS.extend(2.)).with_scale(POND_SCALE),
-        UnderwaterAnimation(Timer::from_seconds(3., TimerMode::Repeating)),
-    ));
-
-    // Sugar cane
-    (0..SUGAR_CANE_COUNT).for_each(|i| {
-        spawn_sugar_cane(
-            &mut commands,
-            &mut rng,
-            assets.textures.clone(),
-            TextureAtlas::from(assets.texture_atlas.clone()),
-            i,
-            0,
-            Side::Left,
-        );
+    spawn_pond(&mut commands, &assets, &clips, POND_POS, OnGambaScreen);
+
+    // Sugar cane, the bottom row plus everything grown on earlier visits
+    for (side, column, height) in sugar_cane_height.stacks() {
+        for row in 0..=height {
+            spawn_sugar_cane(
+                &mut commands,
+                &mut rng,
+                assets.textures.clone(),
+                TextureAtlas::from(assets.texture_atlas.clone()),
+                Outcome {
+                    side: side.clone(),
+                    column,
+                },
+                row,
+            );
+        }
+    }
+}
 
-        spawn_sugar_cane(
-            &mut commands,
-            &mut rng,
-            assets.textures.clone(),
-            TextureAtlas::from(assets.texture_atlas.clone()),
-            i,
-            0,
-            Side::Right,
+/// Spawns the pond's water, underwater, surface and lily layers centred on `position`, each
+/// tagged with `marker`.
+pub fn spawn_pond(
+    commands: &mut Commands,
+    assets: &Assets,
+    clips: &AnimationClips,
+    position: Vec2,
+    marker: impl Component + Clone,
+) {
+    let layer = |index: usize, z: f32| {
+        (
+            Sprite::from_atlas_image(
+                assets.pond.clone(),
+                TextureAtlas::from(assets.pond_atlas.clone()).with_index(index),
+            ),
+            Pond,
+            marker.clone(),
+            Transform::from_translation(position.extend(z)).with_scale(POND_SCALE),
         )
-    });
+    };
+    commands.spawn(layer(POND_WATER_INDEX, 1.));
+    let underwater = clips.get("underwater");
+    commands.spawn((layer(underwater.index(), 2.), underwater));
+    let water_surface = clips.get("water_surface");
+    commands.spawn((layer(water_surface.index(), 3.), water_surface));
+    commands.spawn(layer(POND_LILY_INDEX, 4.));
 }
 
 fn handle_bet_change_messages(
@@ -203,90 +412,262 @@ fn handle_bet_change_messages(
                     bet.0 -= 1;
                 }
             }
+            BetChange::Set(amount) => bet.0 = amount.clamp(1, score.0),
         }
     }
 }
 
-fn handle_bet_messages(
+/// Decides the outcome as soon as a bet comes in, but keeps it secret until the reveal is over.
+fn place_bet(
     mut commands: Commands,
     mut rng: Single<&mut WyRand, With<GlobalRng>>,
-    assets: Res<Assets>,
     mut bet_messages: MessageReader<BetMessage>,
+    assets: Res<Assets>,
+    odds_tables: Res<bevy::asset::Assets<OddsTable>>,
+    credit_terms: Res<bevy::asset::Assets<CreditTerms>>,
+    bet: Res<Bet>,
+    mut score: ResMut<Score>,
+    mut fair: ResMut<ProvablyFair>,
+    session: Res<Session>,
+    safeguard_settings: Res<SafeguardSettings>,
+    canes: Query<Entity, With<Column>>,
+    mut round_state: ResMut<NextState<GambaRoundState>>,
+    mut rejected_messages: MessageWriter<BetRejectedMessage>,
+) {
+    // Only the first bet counts, anything sent alongside it is dropped.
+    let Some(msg) = bet_messages.read().next().cloned() else {
+        return;
+    };
+    bet_messages.clear();
+    if let Some(reason) = session.limit_reached(&safeguard_settings) {
+        rejected_messages.write(BetRejectedMessage { reason });
+        return;
+    }
+    if score.0 == 0 {
+        rejected_messages.write(BetRejectedMessage {
+            reason: "no money left".into(),
+        });
+        return;
+    }
+    // Payout needs both, so the round can't start without them.
+    if !odds_tables.contains(&assets.odds) || !credit_terms.contains(&assets.credit_terms) {
+        rejected_messages.write(BetRejectedMessage {
+            reason: "rules not loaded".into(),
+        });
+        return;
+    }
+
+    let outcome = if fair.enabled {
+        fair.next_outcome()
+    } else {
+        Outcome::from_roll(rng.next_u32())
+    };
+    // The stake goes on the table right away, so it can't be spent while the canes wobble.
+    let stake = bet.0.min(score.0);
+    score.0 -= stake;
+    commands.insert_resource(PendingRound {
+        bet: msg.0,
+        stake,
+        outcome,
+        reveal: Timer::from_seconds(REVEAL_SECS, TimerMode::Once),
+    });
+
+    for cane in &canes {
+        let mut wobble = Tween::new(
+            TweenTarget::Rotation {
+                start: 0.,
+                end: WOBBLE_ANGLE,
+            },
+            WOBBLE_SECS,
+            EaseFunction::SineInOut,
+        )
+        .with_yoyo();
+        for i in 1..REVEAL_WOBBLES {
+            let angle = if i.is_multiple_of(2) {
+                WOBBLE_ANGLE
+            } else {
+                -WOBBLE_ANGLE
+            };
+            wobble = wobble
+                .then(
+                    TweenTarget::Rotation {
+                        start: 0.,
+                        end: angle,
+                    },
+                    WOBBLE_SECS,
+                    EaseFunction::SineInOut,
+                )
+                .with_yoyo();
+        }
+        commands.entity(cane).insert(wobble);
+    }
+    round_state.set(GambaRoundState::Revealing);
+}
+
+fn finish_reveal(
+    time: Res<Time>,
+    mut round: ResMut<PendingRound>,
+    mut round_state: ResMut<NextState<GambaRoundState>>,
+) {
+    round.reveal.tick(time.delta());
+    if round.reveal.is_finished() {
+        round_state.set(GambaRoundState::Payout);
+    }
+}
+
+/// Hands the stake back when the screen is left before the reveal is over.
+fn abandon_round(
+    mut commands: Commands,
+    round: Option<Res<PendingRound>>,
+    mut score: ResMut<Score>,
+) {
+    if let Some(round) = round {
+        score.0 += round.stake;
+        commands.remove_resource::<PendingRound>();
+    }
+}
+
+fn pay_out(
+    mut commands: Commands,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    assets: Res<Assets>,
+    round: Res<PendingRound>,
     mut bet: ResMut<Bet>,
     mut score: ResMut<Score>,
     mut sugar_cane_height: ResMut<SugarCaneHeight>,
-    mut camera_move_state: ResMut<CameraMoveState>,
-    camera_transform: Single<&Transform, With<Camera>>,
+    camera: Single<(Entity, &Transform), With<Camera>>,
+    mut stats: ResMut<Stats>,
+    odds_tables: Res<bevy::asset::Assets<OddsTable>>,
+    credit: Res<Credit>,
+    credit_terms: Res<bevy::asset::Assets<CreditTerms>>,
+    mut result_messages: MessageWriter<BetResultMessage>,
 ) {
-    for msg in bet_messages.read() {
-        let choice = if rng.next_u32().is_multiple_of(2) {
-            Side::Left
-        } else {
-            Side::Right
-        };
-        let sound_effects;
-        if msg.0 == choice {
-            score.0 += bet.0;
-            sound_effects = vec![&assets.win1, &assets.win2, &assets.win3];
-        } else {
-            score.0 -= bet.0;
-            if score.0 == 0 {
-                declare_bankruptcy(&mut commands, &mut rng, &mut score);
-                sound_effects = vec![
-                    &assets.bankrupt1,
-                    &assets.bankrupt2,
-                    &assets.bankrupt3,
-                    &assets.bankrupt4,
-                ];
-            } else {
-                sound_effects = vec![
-                    &assets.loss1,
-                    &assets.loss2,
-                    &assets.loss3,
-                    &assets.loss4,
-                    &assets.loss5,
-                    &assets.loss6,
-                    &assets.loss7,
-                    &assets.loss8,
-                    &assets.loss9,
-                ];
-            }
-            bet.0 = bet.0.min(score.0);
+    let (Some(odds), Some(terms)) = (
+        odds_tables.get(&assets.odds),
+        credit_terms.get(&assets.credit_terms),
+    ) else {
+        return;
+    };
+    let outcome = round.outcome.clone();
+    // The stake was taken when the bet was placed, settling takes it again.
+    score.0 += round.stake;
+    let score_before = score.0;
+    // The bet may have been changed during the reveal, the stake is what was on the table.
+    let mut stake = round.stake;
+    let settlement = settle(
+        &mut score.0,
+        &mut stake,
+        &round.bet,
+        &outcome,
+        odds,
+        false,
+        &mut **rng,
+    );
+    let won = matches!(settlement, Settlement::Won);
+    let sound_effects = match settlement {
+        Settlement::Won => vec![&assets.win1, &assets.win2, &assets.win3],
+        Settlement::Lost => vec![
+            &assets.loss1,
+            &assets.loss2,
+            &assets.loss3,
+            &assets.loss4,
+            &assets.loss5,
+            &assets.loss6,
+            &assets.loss7,
+            &assets.loss8,
+            &assets.loss9,
+        ],
+        Settlement::Bankrupt => {
+            go_broke(
+                &mut commands,
+                &mut **rng,
+                &mut score,
+                &mut stats,
+                &credit,
+                terms,
+            );
+            vec![
+                &assets.bankrupt1,
+                &assets.bankrupt2,
+                &assets.bankrupt3,
+                &assets.bankrupt4,
+            ]
         }
-        commands.spawn(AudioPlayer::new(
-            (*sound_effects.iter().choose(&mut rng).unwrap()).clone(),
+    };
+    bet.0 = bet.0.min(score.0);
+    commands.spawn(AudioPlayer::new(
+        (*sound_effects.iter().choose(&mut rng).unwrap()).clone(),
+    ));
+    result_messages.write(BetResultMessage {
+        round: SettledRound::Gamba {
+            bet: round.bet.clone(),
+            outcome: outcome.clone(),
+        },
+        stake: round.stake,
+        score_before,
+        score_after: score.0,
+        settlement,
+    });
+    commands.remove_resource::<PendingRound>();
+
+    let height = sugar_cane_height.get_mut(&outcome.side, outcome.column);
+    *height += 1;
+    let height = *height;
+
+    let (camera_entity, camera_transform) = *camera;
+    commands.entity(camera_entity).insert(Tween::new(
+        TweenTarget::Translation {
+            start: camera_transform.translation,
+            end: camera_transform
+                .translation
+                .with_y(height as f32 * SUGAR_CANE_SIZE),
+        },
+        2.,
+        EaseFunction::CubicInOut,
+    ));
+    if won {
+        commands.spawn((
+            ParticleEmitter::coins(),
+            Transform::from_translation(cane_translation(&outcome, height).with_z(5.)),
+            OnGambaScreen,
         ));
-        let index = rng.next_u32() as usize % SUGAR_CANE_COUNT;
-        camera_move_state.start = camera_transform.translation.y;
-        camera_move_state.timer = Timer::from_seconds(2., TimerMode::Once);
-        match choice {
-            Side::Left => {
-                sugar_cane_height.left[index] += 1;
-                camera_move_state.target = sugar_cane_height.left[index] as f32 * SUGAR_CANE_SIZE;
-                spawn_sugar_cane(
-                    &mut commands,
-                    &mut rng,
-                    assets.textures.clone(),
-                    TextureAtlas::from(assets.texture_atlas.clone()),
-                    index,
-                    sugar_cane_height.left[index],
-                    choice,
-                );
-            }
-            Side::Right => {
-                sugar_cane_height.right[index] += 1;
-                camera_move_state.target = sugar_cane_height.right[index] as f32 * SUGAR_CANE_SIZE;
-                spawn_sugar_cane(
-                    &mut commands,
-                    &mut rng,
-                    assets.textures.clone(),
-                    TextureAtlas::from(assets.texture_atlas.clone()),
-                    index,
-                    sugar_cane_height.right[index],
-                    choice,
-                );
-            }
-        }
+    }
+    grow_sugar_cane(&mut commands, &mut rng, &assets, outcome, height);
+}
+
+/// Spawns a new sugar cane that springs up out of the one below it.
+fn grow_sugar_cane(
+    commands: &mut Commands,
+    rng: &mut WyRand,
+    assets: &Assets,
+    outcome: Outcome,
+    height: usize,
+) {
+    let cane = spawn_sugar_cane(
+        commands,
+        rng,
+        assets.textures.clone(),
+        TextureAtlas::from(assets.texture_atlas.clone()),
+        outcome,
+        height,
+    );
+    commands.entity(cane).insert(Tween::new(
+        TweenTarget::Scale {
+            start: SUGAR_CANE_SCALE.with_y(0.),
+            end: SUGAR_CANE_SCALE,
+        },
+        0.8,
+        EaseFunction::ElasticOut,
+    ));
+}
+
+/// Opens betting again once the camera has reached the new cane.
+fn finish_payout(
+    moving_camera: Query<(), (With<Camera>, With<Tween>)>,
+    mut round_state: ResMut<NextState<GambaRoundState>>,
+) {
+    if moving_camera.is_empty() {
+        round_state.set(GambaRoundState::Betting);
     }
 }
 
@@ -295,138 +676,326 @@ fn spawn_sugar_cane(
     rng: &mut WyRand,
     textures: Handle<Image>,
     atlas: TextureAtlas,
-    index: usize,
+    outcome: Outcome,
     height: usize,
-    side: Side,
-) {
-    let side_sign = match side {
-        Side::Left => -1.,
-        Side::Right => 1.,
-    };
+) -> Entity {
     let variant = rng.next_u32() as usize % SUGAR_CANE_VARIANTS;
+    let translation = cane_translation(&outcome, height);
     commands
         .spawn((
             Sprite::from_atlas_image(textures, atlas.with_index(SUGAR_CANE_INDEX + variant)),
             OnGambaScreen,
-            side,
+            Column(outcome.column),
+            Row(height),
+            outcome.side,
             Pickable::default(),
-            Transform::from_translation(Vec3::new(
-                side_sign * (index + 1) as f32 * SUGAR_CANE_SIZE,
-                height as f32 * SUGAR_CANE_SIZE,
-                0.,
-            ))
-            .with_scale(SUGAR_CANE_SCALE),
+            Transform::from_translation(translation).with_scale(SUGAR_CANE_SCALE),
         ))
-        .observe(pick_side);
+        .observe(pick_side)
+        .id()
 }
 
+/// Where the cane `height` rows up the stack the outcome grew on stands.
+fn cane_translation(outcome: &Outcome, height: usize) -> Vec3 {
+    let side_sign = match outcome.side {
+        Side::Left => -1.,
+        Side::Right => 1.,
+    };
+    Vec3::new(
+        side_sign * (outcome.column + 1) as f32 * SUGAR_CANE_SIZE,
+        height as f32 * SUGAR_CANE_SIZE,
+        0.,
+    )
+}
+
+#[derive(Component)]
+struct BetOffer;
+
+#[derive(Component)]
+enum BetOfferButton {
+    Place(BetKind),
+    Cancel,
+}
+
+/// Offers every bet that involves the clicked sugar cane next to the pointer.
 fn pick_side(
     event: On<Pointer<Press>>,
-    mut message_writer: MessageWriter<BetMessage>,
-    side_query: Query<&Side>,
+    mut commands: Commands,
+    cane_query: Query<(&Side, &Column)>,
+    offer_query: Query<Entity, With<BetOffer>>,
+    assets: Res<Assets>,
+    odds_tables: Res<bevy::asset::Assets<OddsTable>>,
+    replay: Option<Res<Replay>>,
+    round_state: Option<Res<State<GambaRoundState>>>,
 ) {
-    if let Ok(side) = side_query.get(event.event_target()) {
-        message_writer.write(BetMessage(side.clone()));
+    let Ok((side, column)) = cane_query.get(event.event_target()) else {
+        return;
+    };
+    let Some(odds) = odds_tables.get(&assets.odds) else {
+        return;
+    };
+    if !live_input(replay) || !betting_open(round_state) {
+        return;
+    }
+    for offer in &offer_query {
+        commands.entity(offer).despawn();
     }
-}
-
-#[derive(Component)]
-struct BrokeMessage(Timer);
 
-fn declare_bankruptcy(commands: &mut Commands, rng: &mut WyRand, score: &mut Score) {
-    score.0 = rng.next_u32() as u64 % 5 + 5;
+    let column = column.0;
+    let parity = if column.is_multiple_of(2) {
+        Parity::Odd
+    } else {
+        Parity::Even
+    };
+    let bets = [
+        BetKind::Side(side.clone()),
+        BetKind::Exact {
+            side: side.clone(),
+            column,
+        },
+        BetKind::Column(column),
+        BetKind::AdjacentPair {
+            side: side.clone(),
+            first: column.min(SUGAR_CANE_COUNT - 2),
+        },
+        BetKind::Parity(parity),
+    ];
 
-    let id = commands.register_system(despawn_all::<BrokeMessage>);
-    commands.run_system(id);
+    let position = event.pointer_location.position;
+    commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.),
+                padding: UiRect::all(Val::Px(8.)),
+                position_type: PositionType::Absolute,
+                left: Val::Px(position.x),
+                top: Val::Px(position.y),
+                ..default()
+            },
+            BackgroundColor(Color::srgba_u8(29, 29, 29, 200)),
+            GlobalZIndex(5),
+            BetOffer,
+            OnGambaScreen,
+        ))
+        .with_children(|parent| {
+            for bet in bets {
+                let label = format!("{} (pays {}x)", bet.label(), bet.payout(odds));
+                parent.spawn(bet_offer_button(BetOfferButton::Place(bet), label));
+            }
+            parent.spawn(bet_offer_button(BetOfferButton::Cancel, "cancel".into()));
+        });
+}
 
-    commands.spawn((
+fn bet_offer_button(action: BetOfferButton, label: String) -> impl Bundle {
+    (
+        Button,
+        action,
         Node {
-            align_items: AlignItems::Center,
-            justify_content: JustifyContent::Center,
-            width: Val::Percent(20.),
-            height: Val::Percent(20.),
-            position_type: PositionType::Absolute,
-            bottom: Val::Percent(30.),
-            left: Val::Percent(30.),
+            padding: UiRect::all(Val::Px(4.)),
             ..default()
         },
-        BrokeMessage(Timer::from_seconds(2.5, TimerMode::Once)),
-        OnGambaScreen,
+        BackgroundColor(Color::srgb_u8(69, 69, 69)),
         children![(
-            Text::new(format!("Damn, you're broke! Here, have ${}", score.0)),
+            Text::new(label),
             TextFont {
-                font_size: 24.,
+                font_size: 14.,
                 ..default()
             },
-            TextColor(Color::srgb_u8(29, 29, 29)),
         )],
-    ));
+    )
 }
 
-fn despawn_bankruptcy_messages(
+fn handle_bet_offers(
     mut commands: Commands,
-    mut broke_messages: Query<(Entity, &mut BrokeMessage)>,
-    time: Res<Time>,
+    interaction_query: Query<(&Interaction, &BetOfferButton), Changed<Interaction>>,
+    offer_query: Query<Entity, With<BetOffer>>,
+    mut message_writer: MessageWriter<BetMessage>,
 ) {
-    for (entity, mut msg) in &mut broke_messages {
-        let timer = &mut msg.0;
-        timer.tick(time.delta());
-        if timer.is_finished() {
-            commands.entity(entity).despawn();
+    for (interaction, button) in interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if let BetOfferButton::Place(bet) = button {
+            message_writer.write(BetMessage(bet.clone()));
+        }
+        for offer in &offer_query {
+            commands.entity(offer).despawn();
         }
     }
 }
 
-#[derive(Resource)]
-struct CameraMoveState {
-    start: f32,
-    target: f32,
-    timer: Timer,
-}
+#[derive(Component)]
+struct BrokeMessage;
 
-fn move_camera(
-    mut camera_query: Query<&mut Transform, With<Camera>>,
-    mut state: ResMut<CameraMoveState>,
-    time: Res<Time>,
+/// A [`BrokeMessage`] that stays open until the player takes one of the loans it offers.
+#[derive(Component)]
+struct LoanOfferDialog;
+
+#[derive(Component)]
+struct LoanOfferButton(u64);
+
+/// Shows the bailout the player got or, if they were left with nothing, offers them a loan.
+/// Settles going broke the same way in the gamba and the casino: the bailout or the loan
+/// offers of the credit terms, announced in a popup.
+pub fn go_broke(
+    commands: &mut Commands,
+    rng: &mut impl RngCore,
+    score: &mut Score,
+    stats: &mut Stats,
+    credit: &Credit,
+    terms: &CreditTerms,
 ) {
-    if !state.timer.is_finished() {
-        state.timer.tick(time.delta());
-        for mut transform in &mut camera_query {
-            transform.translation.y = state.start.lerp(state.target, state.timer.fraction());
+    // Nobody is left broke for good, players out of credit still get the bailout.
+    let available = credit.available(terms);
+    let loan_offers = if terms.free_bailout || available == 0 {
+        score.0 = bailout(rng);
+        Vec::new()
+    } else {
+        let mut offers: Vec<u64> = terms
+            .loan_offers
+            .iter()
+            .copied()
+            .filter(|offer| *offer <= available)
+            .collect();
+        if offers.is_empty() {
+            offers.push(available);
         }
-    }
+        offers
+    };
+    declare_bankruptcy(commands, score, stats, loan_offers);
 }
 
-#[derive(Component)]
-struct WaterSurfaceAnimation(Timer);
+fn declare_bankruptcy(
+    commands: &mut Commands,
+    score: &Score,
+    stats: &mut Stats,
+    loan_offers: Vec<u64>,
+) {
+    stats.bankruptcies += 1;
 
-#[derive(Component)]
-struct UnderwaterAnimation(Timer);
+    commands.run_system_cached(despawn_all::<BrokeMessage>);
 
-impl Animation for WaterSurfaceAnimation {
-    fn index() -> usize {
-        6
-    }
+    let grow = Tween::new(
+        TweenTarget::UiScale {
+            start: Vec2::ZERO,
+            end: Vec2::ONE,
+        },
+        0.4,
+        EaseFunction::BackOut,
+    );
+    let node = Node {
+        flex_direction: FlexDirection::Column,
+        align_items: AlignItems::Center,
+        justify_content: JustifyContent::Center,
+        row_gap: Val::Px(8.),
+        width: Val::Percent(20.),
+        height: Val::Percent(20.),
+        position_type: PositionType::Absolute,
+        bottom: Val::Percent(30.),
+        left: Val::Percent(30.),
+        ..default()
+    };
 
-    fn size() -> usize {
-        2
+    if loan_offers.is_empty() {
+        commands.spawn((
+            node,
+            UiTransform::from_scale(Vec2::ZERO),
+            grow.then(
+                TweenTarget::UiScale {
+                    start: Vec2::ONE,
+                    end: Vec2::ONE,
+                },
+                1.7,
+                EaseFunction::Linear,
+            )
+            .then(
+                TweenTarget::UiScale {
+                    start: Vec2::ONE,
+                    end: Vec2::ZERO,
+                },
+                0.4,
+                EaseFunction::BackIn,
+            ),
+            BrokeMessage,
+            children![(
+                Text::new(format!("Damn, you're broke! Here, have ${}", score.0)),
+                TextFont {
+                    font_size: 24.,
+                    ..default()
+                },
+                TextColor(Color::srgb_u8(29, 29, 29)),
+            )],
+        ));
+        return;
     }
 
-    fn timer(&mut self) -> &mut Timer {
-        &mut self.0
-    }
+    commands
+        .spawn((
+            node,
+            UiTransform::from_scale(Vec2::ZERO),
+            grow,
+            BrokeMessage,
+            LoanOfferDialog,
+            children![(
+                Text::new("Damn, you're broke! Need a loan?"),
+                TextFont {
+                    font_size: 24.,
+                    ..default()
+                },
+                TextColor(Color::srgb_u8(29, 29, 29)),
+            )],
+        ))
+        .with_children(|parent| {
+            for offer in loan_offers {
+                parent.spawn((
+                    Button,
+                    LoanOfferButton(offer),
+                    Node {
+                        padding: UiRect::all(Val::Px(4.)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb_u8(69, 69, 69)),
+                    children![(
+                        Text::new(format!("borrow ${offer}")),
+                        TextFont {
+                            font_size: 16.,
+                            ..default()
+                        },
+                    )],
+                ));
+            }
+        });
 }
 
-impl Animation for UnderwaterAnimation {
-    fn index() -> usize {
-        4
+fn handle_loan_offers(
+    interaction_query: Query<(&Interaction, &LoanOfferButton), Changed<Interaction>>,
+    mut credit_messages: MessageWriter<CreditMessage>,
+) {
+    for (interaction, offer) in interaction_query {
+        if *interaction == Interaction::Pressed {
+            credit_messages.write(CreditMessage::Borrow(offer.0));
+        }
     }
+}
 
-    fn size() -> usize {
-        2
+/// Despawns bailout popups once they have shrunk away and the loan offer once the player has
+/// money again, however they got it.
+fn despawn_bankruptcy_messages(
+    mut commands: Commands,
+    mut tween_completed_messages: MessageReader<TweenCompleted>,
+    broke_messages: Query<(), (With<BrokeMessage>, Without<LoanOfferDialog>)>,
+    loan_offers: Query<Entity, With<LoanOfferDialog>>,
+    score: Res<Score>,
+) {
+    for msg in tween_completed_messages.read() {
+        if broke_messages.contains(msg.entity) {
+            commands.entity(msg.entity).despawn();
+        }
     }
-
-    fn timer(&mut self) -> &mut Timer {
-        &mut self.0
+    if score.0 > 0 {
+        for dialog in &loan_offers {
+            commands.entity(dialog).despawn();
+        }
     }
 }
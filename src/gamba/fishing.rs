@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState,
+    animation::{AnimationClips, DespawnOnFinish},
+    assets::Assets,
+    economy::Score,
+    gamba::{Bet, Pond},
+    replay::{Replay, live_input},
+    tween::{Tween, TweenTarget},
+    util::despawn_all,
+};
+
+/// Cast a line into the pond, wait for a bite and reel in whatever took the bait.
+pub struct FishingPlugin;
+
+impl Plugin for FishingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<FishTable>::new(&["fish.ron"]))
+            .init_resource::<FishingLog>()
+            .add_message::<FishingMessage>()
+            .add_observer(click_pond)
+            .add_systems(OnEnter(GameState::Gamba), setup)
+            .add_systems(
+                Update,
+                (
+                    handle_fishing_buttons.run_if(live_input),
+                    let_go.run_if(live_input.and(resource_exists::<Line>)),
+                    handle_fishing_messages,
+                    bob.run_if(resource_exists::<Line>),
+                    update_fishing_panel,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Gamba)),
+            )
+            .add_systems(
+                OnExit(GameState::Gamba),
+                (despawn_all::<OnGambaScreen>, reel_in_line),
+            );
+    }
+}
+
+#[derive(Component)]
+struct OnGambaScreen;
+
+const BOBBER_SIZE: f32 = 8.;
+/// How far the bobber dips while something is biting.
+const BITE_DIP: f32 = 4.;
+const RIPPLE_SCALE: Vec3 = Vec3::splat(2.);
+
+/// Everything that can bite in the pond.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct FishTable {
+    /// Shortest and longest wait for a bite, in seconds.
+    bite_after: (f32, f32),
+    /// What casting the line costs.
+    bait: u64,
+    catches: Vec<Catch>,
+}
+
+#[derive(Deserialize)]
+struct Catch {
+    name: String,
+    /// Relative chance of this being the one that bites.
+    weight: u32,
+    /// What it sells for.
+    price: u64,
+    /// Seconds the player has to reel it in once it bites.
+    window: f32,
+}
+
+impl FishTable {
+    fn price(&self, name: &str) -> u64 {
+        self.catches
+            .iter()
+            .find(|catch| catch.name == name)
+            .map_or(0, |catch| catch.price)
+    }
+
+    fn roll(&self, rng: &mut impl RngCore) -> usize {
+        let total: u32 = self.catches.iter().map(|catch| catch.weight).sum();
+        let mut roll = rng.next_u32() % total.max(1);
+        for (i, catch) in self.catches.iter().enumerate() {
+            if roll < catch.weight {
+                return i;
+            }
+            roll -= catch.weight;
+        }
+        0
+    }
+}
+
+/// Every species caught so far, and the catches not sold yet.
+#[derive(Resource, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct FishingLog {
+    caught: BTreeMap<String, u64>,
+    basket: Vec<String>,
+}
+
+#[derive(Message, Clone, Serialize, Deserialize)]
+pub enum FishingMessage {
+    /// Throws the bobber to a point on the pond.
+    Cast(Vec2),
+    /// Pulls the line out after it has been in the water for `waited` seconds.
+    Reel { waited: f32 },
+    /// Sells the whole basket.
+    Sell,
+}
+
+/// The line currently in the water. What bites, and when, is decided as soon as it lands.
+#[derive(Resource, Clone)]
+struct Line {
+    position: Vec2,
+    elapsed: f32,
+    bite_at: f32,
+    window: f32,
+    catch: usize,
+}
+
+impl Line {
+    fn biting(&self) -> bool {
+        (self.bite_at..=self.bite_at + self.window).contains(&self.elapsed)
+    }
+}
+
+#[derive(Component)]
+struct Bobber;
+
+#[derive(Component)]
+struct FishingStatus;
+
+#[derive(Component)]
+struct FishingLogDisplay;
+
+#[derive(Component)]
+struct SellButton;
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.),
+            padding: UiRect::all(Val::Px(8.)),
+            width: Val::Px(220.),
+            position_type: PositionType::Absolute,
+            top: Val::Percent(40.),
+            left: Val::Px(0.),
+            ..default()
+        },
+        BackgroundColor(Color::srgba_u8(29, 29, 29, 160)),
+        OnGambaScreen,
+        children![
+            (
+                Text::new("click the pond to cast"),
+                TextFont {
+                    font_size: 12.,
+                    ..default()
+                },
+                FishingStatus,
+            ),
+            (
+                Text::default(),
+                TextFont {
+                    font_size: 12.,
+                    ..default()
+                },
+                FishingLogDisplay,
+            ),
+            (
+                Button,
+                SellButton,
+                Node {
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb_u8(69, 69, 69)),
+                children![(
+                    Text::new("sell the basket"),
+                    TextFont {
+                        font_size: 12.,
+                        ..default()
+                    },
+                )],
+            ),
+        ],
+    ));
+}
+
+/// Casts where the pond was clicked, or reels in if the line is already out.
+fn click_pond(
+    event: On<Pointer<Press>>,
+    pond: Query<(), With<Pond>>,
+    line: Option<Res<Line>>,
+    replay: Option<Res<Replay>>,
+    game_state: Option<Res<State<GameState>>>,
+    mut fishing_messages: MessageWriter<FishingMessage>,
+) {
+    if !pond.contains(event.event_target())
+        || game_state.is_none_or(|state| *state.get() != GameState::Gamba)
+        || !live_input(replay)
+    {
+        return;
+    }
+    match line {
+        Some(line) => {
+            fishing_messages.write(FishingMessage::Reel {
+                waited: line.elapsed,
+            });
+        }
+        None => {
+            if let Some(position) = event.hit.position {
+                fishing_messages.write(FishingMessage::Cast(position.truncate()));
+            }
+        }
+    }
+}
+
+fn handle_fishing_buttons(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<SellButton>)>,
+    mut fishing_messages: MessageWriter<FishingMessage>,
+) {
+    for interaction in interaction_query {
+        if *interaction == Interaction::Pressed {
+            fishing_messages.write(FishingMessage::Sell);
+        }
+    }
+}
+
+/// Pulls the line out once the bite is over, so a missed fish still ends the cast.
+fn let_go(line: Res<Line>, mut fishing_messages: MessageWriter<FishingMessage>) {
+    if line.elapsed > line.bite_at + line.window {
+        fishing_messages.write(FishingMessage::Reel {
+            waited: line.elapsed,
+        });
+    }
+}
+
+fn handle_fishing_messages(
+    mut commands: Commands,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    mut fishing_messages: MessageReader<FishingMessage>,
+    current_line: Option<Res<Line>>,
+    mut log: ResMut<FishingLog>,
+    mut score: ResMut<Score>,
+    mut bet: ResMut<Bet>,
+    mut status: Query<&mut Text, With<FishingStatus>>,
+    bobbers: Query<Entity, With<Bobber>>,
+    assets: Res<Assets>,
+    fish_tables: Res<bevy::asset::Assets<FishTable>>,
+    clips: AnimationClips,
+) {
+    let Some(table) = fish_tables.get(&assets.fish) else {
+        return;
+    };
+    let mut line = current_line.as_deref().cloned();
+    let mut changed = false;
+    // A bobber cast this frame isn't in the query yet, but may already be reeled in again.
+    let mut new_bobber = None;
+    let mut message = None;
+    for msg in fishing_messages.read() {
+        match msg {
+            FishingMessage::Cast(position) => {
+                if line.is_some() || table.catches.is_empty() {
+                    continue;
+                }
+                if score.0 <= table.bait {
+                    message = Some(format!("bait costs ${}", table.bait));
+                    continue;
+                }
+                score.0 -= table.bait;
+                let (shortest, longest) = table.bite_after;
+                let roll = rng.next_u32() as f32 / u32::MAX as f32;
+                let catch = table.roll(&mut **rng);
+                line = Some(Line {
+                    position: *position,
+                    elapsed: 0.,
+                    bite_at: shortest + (longest - shortest) * roll,
+                    window: table.catches[catch].window,
+                    catch,
+                });
+                changed = true;
+                new_bobber = Some(
+                    commands
+                        .spawn((
+                            Sprite::from_color(
+                                Color::srgb_u8(220, 48, 48),
+                                Vec2::splat(BOBBER_SIZE),
+                            ),
+                            Transform::from_translation(position.extend(5.)),
+                            Bobber,
+                            OnGambaScreen,
+                        ))
+                        .id(),
+                );
+                spawn_ripple(&mut commands, &assets, &clips, *position);
+                message = Some("waiting for a bite...".to_string());
+            }
+            FishingMessage::Reel { waited } => {
+                let Some(cast) = line.take() else {
+                    continue;
+                };
+                changed = true;
+                for bobber in bobbers.iter().chain(new_bobber.take()) {
+                    commands.entity(bobber).despawn();
+                }
+                message = Some(if *waited < cast.bite_at {
+                    "too early, nothing bit yet".to_string()
+                } else if *waited > cast.bite_at + cast.window {
+                    "too slow, it got away".to_string()
+                } else {
+                    let catch = &table.catches[cast.catch];
+                    *log.caught.entry(catch.name.clone()).or_default() += 1;
+                    log.basket.push(catch.name.clone());
+                    if catch.price > 0 {
+                        commands.spawn(AudioPlayer(assets.win1.clone()));
+                    }
+                    format!("caught: {}!", catch.name)
+                });
+            }
+            FishingMessage::Sell => {
+                if log.basket.is_empty() {
+                    continue;
+                }
+                let total: u64 = log.basket.iter().map(|name| table.price(name)).sum();
+                score.0 = score.0.saturating_add(total);
+                message = Some(format!("sold {} catches for ${total}", log.basket.len()));
+                log.basket.clear();
+            }
+        }
+    }
+
+    if bet.0 > score.0 {
+        bet.0 = score.0;
+    }
+    if changed {
+        match line {
+            Some(line) => commands.insert_resource(line),
+            None => commands.remove_resource::<Line>(),
+        }
+    }
+    if let Some(message) = message {
+        for mut text in &mut status {
+            **text = message.clone();
+        }
+    }
+}
+
+/// Spawns a ring of water spreading out from `position`.
+fn spawn_ripple(commands: &mut Commands, assets: &Assets, clips: &AnimationClips, position: Vec2) {
+    let ripple = clips.get("ripple");
+    commands.spawn((
+        Sprite::from_atlas_image(
+            assets.pond.clone(),
+            TextureAtlas::from(assets.pond_atlas.clone()).with_index(ripple.index()),
+        ),
+        Transform::from_translation(position.extend(4.5)).with_scale(Vec3::splat(0.5)),
+        Tween::new(
+            TweenTarget::Scale {
+                start: Vec3::splat(0.5),
+                end: RIPPLE_SCALE,
+            },
+            0.6,
+            EaseFunction::QuadraticOut,
+        ),
+        ripple,
+        DespawnOnFinish,
+        OnGambaScreen,
+    ));
+}
+
+/// Lets the line sit in the water, dipping the bobber while something bites.
+fn bob(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut line: ResMut<Line>,
+    mut bobber: Query<&mut Transform, With<Bobber>>,
+    mut status: Query<&mut Text, With<FishingStatus>>,
+    assets: Res<Assets>,
+    clips: AnimationClips,
+) {
+    let was_biting = line.biting();
+    line.elapsed += time.delta_secs();
+    let biting = line.biting();
+    for mut transform in &mut bobber {
+        let wobble = (line.elapsed * 6.).sin();
+        transform.translation.y = line.position.y
+            + if biting {
+                -BITE_DIP * (1. + (line.elapsed * 30.).sin()) / 2.
+            } else {
+                wobble
+            };
+    }
+    if biting && !was_biting {
+        spawn_ripple(&mut commands, &assets, &clips, line.position);
+        for mut text in &mut status {
+            **text = "something bites, click the pond!".into();
+        }
+    }
+}
+
+fn update_fishing_panel(
+    log: Res<FishingLog>,
+    assets: Res<Assets>,
+    fish_tables: Res<bevy::asset::Assets<FishTable>>,
+    mut display: Query<&mut Text, With<FishingLogDisplay>>,
+    spawned: Query<(), Added<FishingLogDisplay>>,
+) {
+    if !log.is_changed() && spawned.is_empty() {
+        return;
+    }
+    let Some(table) = fish_tables.get(&assets.fish) else {
+        return;
+    };
+    let worth: u64 = log.basket.iter().map(|name| table.price(name)).sum();
+    let mut lines = vec![
+        format!("bait: ${} a cast", table.bait),
+        format!("basket: {} (worth ${worth})", log.basket.len()),
+        format!("collection: {}/{}", log.caught.len(), table.catches.len()),
+    ];
+    lines.extend(
+        table
+            .catches
+            .iter()
+            .map(|catch| match log.caught.get(&catch.name) {
+                Some(count) => format!("  {} x{count}", catch.name),
+                None => "  ???".to_string(),
+            }),
+    );
+    for mut text in &mut display {
+        **text = lines.join("\n");
+    }
+}
+
+fn reel_in_line(mut commands: Commands) {
+    commands.remove_resource::<Line>();
+}
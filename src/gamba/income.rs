@@ -0,0 +1,258 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState,
+    economy::Score,
+    gamba::market::Market,
+    replay::live_input,
+    util::{despawn_all, unix_time},
+};
+
+/// Canes bought on the market pay out a little every second, and some more for the time the game
+/// was closed.
+pub struct IncomePlugin;
+
+impl Plugin for IncomePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Income>()
+            .insert_resource(IncomeClock {
+                payday: Timer::from_seconds(PAYDAY_SECS, TimerMode::Repeating),
+                check_in: Timer::from_seconds(CHECK_IN_SECS, TimerMode::Repeating),
+            })
+            .add_message::<IncomeMessage>()
+            .add_systems(
+                OnExit(GameState::AssetLoading),
+                welcome_back.run_if(live_input),
+            )
+            .add_systems(OnEnter(GameState::Gamba), setup)
+            .add_systems(
+                Update,
+                (
+                    tick_income.run_if(live_input),
+                    handle_income_messages,
+                    (update_income_display, dismiss_away_popup),
+                )
+                    .chain()
+                    .run_if(not(in_state(GameState::AssetLoading))),
+            )
+            .add_systems(OnExit(GameState::Gamba), despawn_all::<OnGambaScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnGambaScreen;
+
+/// Dollars a single owned cane earns per second in the gamba.
+const INCOME_PER_CANE: f32 = 0.02;
+/// Share of the usual income paid for the time the game was closed.
+const OFFLINE_SHARE: f32 = 0.5;
+/// Longest stretch of offline time that still pays.
+const OFFLINE_CAP_SECS: u64 = 8 * 60 * 60;
+const PAYDAY_SECS: f32 = 1.;
+/// How often the time of the last visit is updated.
+const CHECK_IN_SECS: f32 = 30.;
+
+/// Income not paid out yet, and when the game was last running to work out offline time.
+#[derive(Resource, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Income {
+    /// Unix time in seconds, 0 before the very first launch.
+    last_seen: u64,
+    /// Fraction of a dollar earned so far.
+    owed: f32,
+}
+
+#[derive(Resource)]
+struct IncomeClock {
+    payday: Timer,
+    check_in: Timer,
+}
+
+#[derive(Message, Clone, Serialize, Deserialize)]
+pub enum IncomeMessage {
+    /// Pays for a second spent in the gamba.
+    Payday,
+    /// Pays for the time the game was closed.
+    Offline { seconds: u64 },
+}
+
+#[derive(Component)]
+struct IncomeDisplay;
+
+#[derive(Component)]
+struct AwayPopup;
+
+#[derive(Component)]
+struct DismissButton;
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(0.),
+            left: Val::Px(250.),
+            padding: UiRect::all(Val::Px(4.)),
+            ..default()
+        },
+        OnGambaScreen,
+        children![(
+            Text::default(),
+            TextFont {
+                font_size: 12.,
+                ..default()
+            },
+            IncomeDisplay,
+        )],
+    ));
+}
+
+/// Works out how long the game was closed, right before the recorded part of the session starts.
+fn welcome_back(mut income: ResMut<Income>, mut income_messages: MessageWriter<IncomeMessage>) {
+    let now = unix_time();
+    if income.last_seen > 0 {
+        let seconds = now.saturating_sub(income.last_seen).min(OFFLINE_CAP_SECS);
+        income_messages.write(IncomeMessage::Offline { seconds });
+    }
+    income.last_seen = now;
+}
+
+fn tick_income(
+    time: Res<Time>,
+    mut clock: ResMut<IncomeClock>,
+    mut income: ResMut<Income>,
+    game_state: Res<State<GameState>>,
+    mut income_messages: MessageWriter<IncomeMessage>,
+) {
+    clock.check_in.tick(time.delta());
+    if clock.check_in.just_finished() {
+        income.last_seen = unix_time();
+    }
+    if *game_state.get() != GameState::Gamba {
+        return;
+    }
+    clock.payday.tick(time.delta());
+    for _ in 0..clock.payday.times_finished_this_tick() {
+        income_messages.write(IncomeMessage::Payday);
+    }
+}
+
+fn handle_income_messages(
+    mut commands: Commands,
+    mut income_messages: MessageReader<IncomeMessage>,
+    mut income: ResMut<Income>,
+    mut score: ResMut<Score>,
+    market: Res<Market>,
+) {
+    let per_second = market.owned() as f32 * INCOME_PER_CANE;
+    for msg in income_messages.read() {
+        let earned = match msg {
+            IncomeMessage::Payday => per_second * PAYDAY_SECS,
+            IncomeMessage::Offline { seconds } => {
+                let earned = per_second * OFFLINE_SHARE * *seconds as f32;
+                if earned >= 1. {
+                    spawn_away_popup(&mut commands, *seconds, earned.floor() as u64);
+                }
+                earned
+            }
+        };
+        if earned <= 0. {
+            continue;
+        }
+        // Fractions of a dollar only carry over, they aren't worth marking anything for a save.
+        income.bypass_change_detection().owed += earned;
+        let whole = income.owed.floor();
+        if whole > 0. {
+            income.owed -= whole;
+            score.0 = score.0.saturating_add(whole as u64);
+        }
+    }
+}
+
+fn spawn_away_popup(commands: &mut Commands, seconds: u64, earned: u64) {
+    let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+    commands.spawn((
+        Node {
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        BackgroundColor(Color::srgba_u8(0, 0, 0, 160)),
+        GlobalZIndex(20),
+        AwayPopup,
+        children![(
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(12.),
+                padding: UiRect::all(Val::Px(24.)),
+                ..default()
+            },
+            BackgroundColor(Color::srgb_u8(200, 200, 200)),
+            children![
+                (
+                    Text::new(format!(
+                        "while you were away ({hours}h {minutes}m)\nyour sugar canes earned ${earned}"
+                    )),
+                    TextFont {
+                        font_size: 24.,
+                        ..default()
+                    },
+                    TextColor(Color::srgb_u8(29, 29, 29)),
+                    TextLayout::new_with_justify(Justify::Center),
+                ),
+                (
+                    Button,
+                    DismissButton,
+                    Node {
+                        padding: UiRect::all(Val::Px(8.)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb_u8(69, 69, 69)),
+                    children![(
+                        Text::new("nice"),
+                        TextFont {
+                            font_size: 20.,
+                            ..default()
+                        },
+                    )],
+                ),
+            ],
+        )],
+    ));
+}
+
+fn update_income_display(
+    market: Res<Market>,
+    mut display: Query<&mut Text, With<IncomeDisplay>>,
+    spawned: Query<(), Added<IncomeDisplay>>,
+) {
+    if !market.is_changed() && spawned.is_empty() {
+        return;
+    }
+    let canes = market.owned();
+    for mut text in &mut display {
+        **text = format!(
+            "{canes} owned canes earn ${:.2}/min",
+            canes as f32 * INCOME_PER_CANE * 60.
+        );
+    }
+}
+
+fn dismiss_away_popup(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<DismissButton>)>,
+    popups: Query<Entity, With<AwayPopup>>,
+) {
+    if interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        for popup in &popups {
+            commands.entity(popup).despawn();
+        }
+    }
+}
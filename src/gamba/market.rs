@@ -0,0 +1,460 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_rand::prelude::*;
+use rand_core::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState,
+    economy::Score,
+    gamba::{Bet, CaneField, Sign},
+    replay::live_input,
+    util::despawn_all,
+};
+
+/// Trade the grown sugar canes for score at a price that never sits still.
+pub struct MarketPlugin;
+
+impl Plugin for MarketPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Market>()
+            .insert_resource(MarketClock(Timer::from_seconds(
+                TICK_SECS,
+                TimerMode::Repeating,
+            )))
+            .add_message::<MarketMessage>()
+            // After the field is planted, so the seed is drawn in the same order every time.
+            .add_systems(OnEnter(GameState::Gamba), setup.after(super::setup))
+            .add_systems(
+                Update,
+                (
+                    (tick_market, handle_market_buttons).run_if(live_input),
+                    handle_market_messages,
+                    draw_chart,
+                    update_market_panel,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Gamba)),
+            )
+            .add_systems(OnExit(GameState::Gamba), despawn_all::<OnGambaScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnGambaScreen;
+
+const TICK_SECS: f32 = 1.5;
+/// Ticks that make up one candle of the chart.
+const CANDLE_TICKS: u64 = 5;
+const CHART_CANDLES: usize = 9;
+/// Price the walk is pulled back towards.
+const BASE_PRICE: f32 = 5.;
+const MIN_PRICE: f32 = 1.;
+const MAX_PRICE: f32 = 100.;
+/// Standard deviation of the relative price change per tick.
+const VOLATILITY: f32 = 0.04;
+/// Share of the distance to the base price (in log terms) closed every tick.
+const REVERSION: f32 = 0.02;
+const SHOCK_CHANCE: f32 = 0.03;
+/// Smallest and largest relative jump of a shock.
+const SHOCK_SIZE: (f32, f32) = (0.15, 0.4);
+const MAX_ORDERS: usize = 10;
+/// Where the chart sits on the sign's board, in the sign's own pixels.
+const CHART_CENTER: Vec2 = Vec2::new(0., 9.);
+const CHART_SIZE: Vec2 = Vec2::new(28., 11.);
+
+/// Price history of one stretch of [`CANDLE_TICKS`] ticks.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct Candle {
+    open: f32,
+    high: f32,
+    low: f32,
+    close: f32,
+}
+
+impl Candle {
+    fn at(price: f32) -> Self {
+        Self {
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+        }
+    }
+}
+
+/// A standing order for a single cane, filled on the first tick its price is met.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum LimitOrder {
+    Buy { at_most: u64 },
+    Sell { at_least: u64 },
+}
+
+/// The cane price and everything needed to carry its walk on, kept between visits.
+#[derive(Resource, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Market {
+    seed: u64,
+    ticks: u64,
+    price: f32,
+    /// Recent candles, the last one still forming.
+    candles: VecDeque<Candle>,
+    orders: Vec<LimitOrder>,
+    /// Canes bought here. Canes grown by bets are only for show, so these are all that can be
+    /// sold back.
+    owned: u64,
+}
+
+impl Default for Market {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            ticks: 0,
+            price: BASE_PRICE,
+            candles: VecDeque::from([Candle::at(BASE_PRICE)]),
+            orders: Vec::new(),
+            owned: 0,
+        }
+    }
+}
+
+impl Market {
+    /// Canes bought on the market and not sold yet.
+    pub fn owned(&self) -> u64 {
+        self.owned
+    }
+
+    /// What a cane costs to buy.
+    fn ask(&self) -> u64 {
+        self.price.ceil() as u64
+    }
+
+    /// What a cane sells for.
+    fn bid(&self) -> u64 {
+        self.price.floor() as u64
+    }
+
+    /// Moves the price one step. Every step has its own RNG derived from the seed, so the walk
+    /// only depends on the seed and the number of ticks.
+    fn step(&mut self) {
+        let mut rng = WyRand::seed_from_u64(self.seed.wrapping_add(self.ticks));
+        let mut unit = || rng.next_u32() as f32 / u32::MAX as f32;
+        // The sum of four uniform rolls is close enough to a normal distribution.
+        let noise = ((0..4).map(|_| unit()).sum::<f32>() - 2.) * 3f32.sqrt();
+        let mut change = VOLATILITY * noise + REVERSION * (BASE_PRICE / self.price).ln();
+        if unit() < SHOCK_CHANCE {
+            let size = SHOCK_SIZE.0 + (SHOCK_SIZE.1 - SHOCK_SIZE.0) * unit();
+            change += if unit() < 0.5 { -size } else { size };
+        }
+        self.price = (self.price * change.exp()).clamp(MIN_PRICE, MAX_PRICE);
+        self.ticks += 1;
+
+        if self.ticks.is_multiple_of(CANDLE_TICKS) || self.candles.is_empty() {
+            self.candles.push_back(Candle::at(self.price));
+            if self.candles.len() > CHART_CANDLES {
+                self.candles.pop_front();
+            }
+        } else if let Some(candle) = self.candles.back_mut() {
+            candle.high = candle.high.max(self.price);
+            candle.low = candle.low.min(self.price);
+            candle.close = self.price;
+        }
+    }
+}
+
+/// Drives the market while playing live, recorded so replays move the price at the same moments.
+#[derive(Resource)]
+struct MarketClock(Timer);
+
+#[derive(Message, Clone, Serialize, Deserialize)]
+pub enum MarketMessage {
+    /// Moves the price on and fills any orders it now meets.
+    Tick,
+    Buy,
+    Sell,
+    PlaceOrder(LimitOrder),
+    CancelOrders,
+}
+
+#[derive(Component)]
+enum MarketButton {
+    Buy,
+    Sell,
+    BuyBelow,
+    SellAbove,
+    CancelOrders,
+}
+
+#[derive(Component)]
+struct MarketDisplay;
+
+#[derive(Component)]
+struct ChartPart;
+
+fn setup(
+    mut commands: Commands,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    mut market: ResMut<Market>,
+) {
+    if market.seed == 0 {
+        market.seed = rng.next_u64();
+    }
+
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.),
+            padding: UiRect::all(Val::Px(8.)),
+            width: Val::Px(220.),
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(170.),
+            right: Val::Px(0.),
+            ..default()
+        },
+        BackgroundColor(Color::srgba_u8(29, 29, 29, 160)),
+        OnGambaScreen,
+        children![
+            (
+                Text::default(),
+                TextFont {
+                    font_size: 12.,
+                    ..default()
+                },
+                MarketDisplay,
+            ),
+            (
+                Node {
+                    flex_wrap: FlexWrap::Wrap,
+                    column_gap: Val::Px(4.),
+                    row_gap: Val::Px(4.),
+                    ..default()
+                },
+                children![
+                    market_button(MarketButton::Buy, "buy"),
+                    market_button(MarketButton::Sell, "sell"),
+                    market_button(MarketButton::BuyBelow, "buy $1 lower"),
+                    market_button(MarketButton::SellAbove, "sell $1 higher"),
+                    market_button(MarketButton::CancelOrders, "cancel orders"),
+                ],
+            ),
+        ],
+    ));
+}
+
+fn market_button(action: MarketButton, label: &str) -> impl Bundle + use<> {
+    (
+        Button,
+        action,
+        Node {
+            padding: UiRect::all(Val::Px(4.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgb_u8(69, 69, 69)),
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size: 12.,
+                ..default()
+            },
+        )],
+    )
+}
+
+fn tick_market(
+    time: Res<Time>,
+    mut clock: ResMut<MarketClock>,
+    mut market_messages: MessageWriter<MarketMessage>,
+) {
+    clock.0.tick(time.delta());
+    for _ in 0..clock.0.times_finished_this_tick() {
+        market_messages.write(MarketMessage::Tick);
+    }
+}
+
+fn handle_market_buttons(
+    interaction_query: Query<(&Interaction, &MarketButton), Changed<Interaction>>,
+    market: Res<Market>,
+    mut market_messages: MessageWriter<MarketMessage>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction == Interaction::Pressed {
+            market_messages.write(match button {
+                MarketButton::Buy => MarketMessage::Buy,
+                MarketButton::Sell => MarketMessage::Sell,
+                MarketButton::BuyBelow => MarketMessage::PlaceOrder(LimitOrder::Buy {
+                    at_most: market.ask().saturating_sub(1).max(1),
+                }),
+                MarketButton::SellAbove => MarketMessage::PlaceOrder(LimitOrder::Sell {
+                    at_least: market.bid() + 1,
+                }),
+                MarketButton::CancelOrders => MarketMessage::CancelOrders,
+            });
+        }
+    }
+}
+
+/// Buys a cane at the asking price, always keeping $1 to bet with.
+fn buy(market: &mut Market, field: &mut CaneField, score: &mut Score) -> bool {
+    if score.0 <= market.ask() {
+        return false;
+    }
+    score.0 -= market.ask();
+    field.plant();
+    market.owned += 1;
+    true
+}
+
+fn sell(market: &mut Market, field: &mut CaneField, score: &mut Score) -> bool {
+    if market.owned == 0 || !field.cut() {
+        return false;
+    }
+    market.owned -= 1;
+    score.0 = score.0.saturating_add(market.bid());
+    true
+}
+
+fn handle_market_messages(
+    mut market_messages: MessageReader<MarketMessage>,
+    mut market: ResMut<Market>,
+    mut field: CaneField,
+    mut score: ResMut<Score>,
+    mut bet: ResMut<Bet>,
+) {
+    for msg in market_messages.read() {
+        match msg {
+            MarketMessage::Tick => {
+                market.step();
+                let orders = std::mem::take(&mut market.orders);
+                for order in orders {
+                    let filled = match order {
+                        LimitOrder::Buy { at_most } => {
+                            market.ask() <= at_most && buy(&mut market, &mut field, &mut score)
+                        }
+                        LimitOrder::Sell { at_least } => {
+                            market.bid() >= at_least && sell(&mut market, &mut field, &mut score)
+                        }
+                    };
+                    if !filled {
+                        market.orders.push(order);
+                    }
+                }
+            }
+            MarketMessage::Buy => {
+                buy(&mut market, &mut field, &mut score);
+            }
+            MarketMessage::Sell => {
+                sell(&mut market, &mut field, &mut score);
+            }
+            MarketMessage::PlaceOrder(order) => {
+                if market.orders.len() < MAX_ORDERS {
+                    market.orders.push(*order);
+                }
+            }
+            MarketMessage::CancelOrders => market.orders.clear(),
+        }
+    }
+    if bet.0 > score.0 {
+        bet.0 = score.0;
+    }
+}
+
+/// Redraws the candlestick chart on the sign's board whenever the price moves.
+fn draw_chart(
+    mut commands: Commands,
+    market: Res<Market>,
+    sign: Query<Entity, With<Sign>>,
+    new_sign: Query<(), Added<Sign>>,
+    parts: Query<Entity, With<ChartPart>>,
+) {
+    if !market.is_changed() && new_sign.is_empty() {
+        return;
+    }
+    let Ok(sign) = sign.single() else {
+        return;
+    };
+    for part in &parts {
+        commands.entity(part).despawn();
+    }
+
+    let low = market
+        .candles
+        .iter()
+        .map(|candle| candle.low)
+        .fold(f32::MAX, f32::min);
+    let high = market
+        .candles
+        .iter()
+        .map(|candle| candle.high)
+        .fold(f32::MIN, f32::max);
+    let range = (high - low).max(f32::EPSILON);
+    let y = |price: f32| CHART_CENTER.y + ((price - low) / range - 0.5) * CHART_SIZE.y;
+    let slot = CHART_SIZE.x / CHART_CANDLES as f32;
+
+    commands.entity(sign).with_children(|parent| {
+        parent.spawn((
+            Sprite::from_color(Color::srgb_u8(29, 29, 29), CHART_SIZE + 2.),
+            Transform::from_translation(CHART_CENTER.extend(0.1)),
+            ChartPart,
+        ));
+        for (i, candle) in market.candles.iter().enumerate() {
+            let x = CHART_CENTER.x - CHART_SIZE.x / 2. + slot * (i as f32 + 0.5);
+            let color = if candle.close >= candle.open {
+                Color::srgb_u8(0, 200, 64)
+            } else {
+                Color::srgb_u8(220, 48, 48)
+            };
+            let (wick_bottom, wick_top) = (y(candle.low), y(candle.high));
+            parent.spawn((
+                Sprite::from_color(color, Vec2::new(0.5, (wick_top - wick_bottom).max(0.5))),
+                Transform::from_xyz(x, (wick_bottom + wick_top) / 2., 0.2),
+                ChartPart,
+            ));
+            let (body_bottom, body_top) = (
+                y(candle.open.min(candle.close)),
+                y(candle.open.max(candle.close)),
+            );
+            parent.spawn((
+                Sprite::from_color(
+                    color,
+                    Vec2::new(slot * 0.6, (body_top - body_bottom).max(1.)),
+                ),
+                Transform::from_xyz(x, (body_bottom + body_top) / 2., 0.3),
+                ChartPart,
+            ));
+        }
+    });
+}
+
+fn update_market_panel(
+    market: Res<Market>,
+    mut display: Query<&mut Text, With<MarketDisplay>>,
+    spawned: Query<(), Added<MarketDisplay>>,
+) {
+    if !market.is_changed() && spawned.is_empty() {
+        return;
+    }
+    let holdings = market.owned;
+    let orders = market
+        .orders
+        .iter()
+        .map(|order| match order {
+            LimitOrder::Buy { at_most } => format!("buy at ${at_most}"),
+            LimitOrder::Sell { at_least } => format!("sell at ${at_least}"),
+        })
+        .collect::<Vec<_>>();
+    for mut text in &mut display {
+        **text = format!(
+            "cane price: ${:.2}\nbuy ${} / sell ${}\nholdings: {holdings} canes (worth ${})\norders: {}",
+            market.price,
+            market.ask(),
+            market.bid(),
+            holdings * market.bid(),
+            if orders.is_empty() {
+                "none".to_string()
+            } else {
+                orders.join(", ")
+            },
+        );
+    }
+}
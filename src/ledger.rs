@@ -0,0 +1,369 @@
+use bevy::prelude::*;
+
+use crate::{
+    GameState,
+    gamba::{BetResultMessage, SettledRound},
+    rules::Settlement,
+    storage,
+    util::despawn_all,
+};
+
+pub struct LedgerPlugin;
+
+impl Plugin for LedgerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Ledger>()
+            .add_systems(OnEnter(GameState::Gamba), setup)
+            .add_systems(
+                Update,
+                record_bets.run_if(in_state(GameState::Gamba).or(in_state(GameState::Casino))),
+            )
+            .add_systems(
+                Update,
+                (handle_ledger_buttons, update_ledger_panel)
+                    .chain()
+                    .after(record_bets)
+                    .run_if(in_state(GameState::Gamba)),
+            )
+            .add_systems(OnExit(GameState::Gamba), despawn_all::<OnGambaScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnGambaScreen;
+
+const LEDGER_FILE: &str = "ledger.csv";
+const CHART_WIDTH: f32 = 240.;
+const CHART_HEIGHT: f32 = 80.;
+/// Most points drawn in the score chart; longer histories are sampled evenly.
+const CHART_POINTS: usize = 60;
+
+/// Every gamba and casino round played this session.
+#[derive(Resource, Default)]
+struct Ledger {
+    entries: Vec<LedgerEntry>,
+}
+
+struct LedgerEntry {
+    /// Seconds since the game started.
+    time: f32,
+    round: SettledRound,
+    stake: u64,
+    score_before: u64,
+    score_after: u64,
+    settlement: Settlement,
+}
+
+impl LedgerEntry {
+    /// Money won or lost on the bet itself, leaving out any bailout.
+    fn profit(&self) -> i64 {
+        match self.settlement {
+            Settlement::Won | Settlement::Lost => {
+                self.score_after as i64 - self.score_before as i64
+            }
+            Settlement::Bankrupt => -(self.stake as i64),
+        }
+    }
+
+    /// Went broke and was handed free money.
+    fn is_bailout(&self) -> bool {
+        self.settlement == Settlement::Bankrupt && self.score_after > 0
+    }
+
+    /// Went broke and was offered a loan instead, which shows up in the debt, not the score.
+    fn is_loan(&self) -> bool {
+        self.settlement == Settlement::Bankrupt && self.score_after == 0
+    }
+}
+
+impl Ledger {
+    fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "round,time,bet,outcome,stake,score_before,score_after,won,bailout,loan\n",
+        );
+        for (round, entry) in self.entries.iter().enumerate() {
+            csv += &format!(
+                "{},{:.2},{},{},{},{},{},{},{},{}\n",
+                round + 1,
+                entry.time,
+                csv_field(&entry.round.bet_label()),
+                csv_field(&entry.round.outcome_label()),
+                entry.stake,
+                entry.score_before,
+                entry.score_after,
+                entry.settlement == Settlement::Won,
+                entry.is_bailout(),
+                entry.is_loan(),
+            );
+        }
+        csv
+    }
+
+    fn summary(&self) -> String {
+        if self.entries.is_empty() {
+            return "no rounds played yet".into();
+        }
+        let wins = self
+            .entries
+            .iter()
+            .filter(|e| e.settlement == Settlement::Won)
+            .count();
+        let (mut longest_win, mut longest_loss, mut streak) = (0, 0, 0i64);
+        for entry in &self.entries {
+            streak = match (entry.settlement == Settlement::Won, streak) {
+                (true, s) if s > 0 => s + 1,
+                (true, _) => 1,
+                (false, s) if s < 0 => s - 1,
+                (false, _) => -1,
+            };
+            longest_win = longest_win.max(streak);
+            longest_loss = longest_loss.max(-streak);
+        }
+        let profits = self.entries.iter().map(LedgerEntry::profit);
+        format!(
+            "rounds: {}\nwin rate: {:.1}%\ncurrent streak: {}\nlongest streaks: {} wins, {} losses\nnet profit: {:+} $\nbiggest win: ${}\nbiggest loss: ${}",
+            self.entries.len(),
+            wins as f32 / self.entries.len() as f32 * 100.,
+            if streak > 0 {
+                format!("{streak} wins")
+            } else {
+                format!("{} losses", -streak)
+            },
+            longest_win,
+            longest_loss,
+            profits.clone().sum::<i64>(),
+            profits.clone().max().unwrap_or(0).max(0),
+            -profits.min().unwrap_or(0).min(0),
+        )
+    }
+
+    /// Score before the first round and after every round, sampled down to [`CHART_POINTS`].
+    fn chart_points(&self) -> Vec<u64> {
+        let scores: Vec<u64> = self
+            .entries
+            .first()
+            .map(|e| e.score_before)
+            .into_iter()
+            .chain(self.entries.iter().map(|e| e.score_after))
+            .collect();
+        if scores.len() <= CHART_POINTS {
+            return scores;
+        }
+        (0..CHART_POINTS)
+            .map(|i| scores[i * (scores.len() - 1) / (CHART_POINTS - 1)])
+            .collect()
+    }
+}
+
+/// Quotes `field` if it would otherwise break the row apart.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+#[derive(Component)]
+enum LedgerButton {
+    Toggle,
+    Export,
+}
+
+#[derive(Component)]
+struct LedgerPanel;
+
+#[derive(Component)]
+struct LedgerStatsDisplay;
+
+#[derive(Component)]
+struct LedgerChart;
+
+#[derive(Component)]
+struct ExportDisplay;
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::End,
+            row_gap: Val::Px(4.),
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.),
+            right: Val::Px(0.),
+            ..default()
+        },
+        OnGambaScreen,
+        children![
+            (
+                Button,
+                LedgerButton::Toggle,
+                Node {
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb_u8(69, 69, 69)),
+                children![(
+                    Text::new("stats"),
+                    TextFont {
+                        font_size: 16.,
+                        ..default()
+                    },
+                )],
+            ),
+            (
+                Node {
+                    display: Display::None,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.),
+                    padding: UiRect::all(Val::Px(8.)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba_u8(29, 29, 29, 160)),
+                LedgerPanel,
+                children![
+                    (
+                        Text::default(),
+                        TextFont {
+                            font_size: 12.,
+                            ..default()
+                        },
+                        LedgerStatsDisplay,
+                    ),
+                    (
+                        Node {
+                            width: Val::Px(CHART_WIDTH),
+                            height: Val::Px(CHART_HEIGHT),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgba_u8(0, 0, 0, 80)),
+                        LedgerChart,
+                    ),
+                    (
+                        Button,
+                        LedgerButton::Export,
+                        Node {
+                            padding: UiRect::all(Val::Px(4.)),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb_u8(69, 69, 69)),
+                        children![(
+                            Text::new("export CSV"),
+                            TextFont {
+                                font_size: 12.,
+                                ..default()
+                            },
+                        )],
+                    ),
+                    (
+                        Text::default(),
+                        TextFont {
+                            font_size: 10.,
+                            ..default()
+                        },
+                        ExportDisplay,
+                    ),
+                ],
+            ),
+        ],
+    ));
+}
+
+fn record_bets(
+    time: Res<Time>,
+    mut result_messages: MessageReader<BetResultMessage>,
+    mut ledger: ResMut<Ledger>,
+) {
+    for result in result_messages.read() {
+        ledger.entries.push(LedgerEntry {
+            time: time.elapsed_secs(),
+            round: result.round.clone(),
+            stake: result.stake,
+            score_before: result.score_before,
+            score_after: result.score_after,
+            settlement: result.settlement,
+        });
+    }
+}
+
+fn handle_ledger_buttons(
+    interaction_query: Query<(&Interaction, &LedgerButton), Changed<Interaction>>,
+    mut panel: Single<&mut Node, With<LedgerPanel>>,
+    ledger: Res<Ledger>,
+    mut export_display: Single<&mut Text, With<ExportDisplay>>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            LedgerButton::Toggle => {
+                panel.display = match panel.display {
+                    Display::None => Display::Flex,
+                    _ => Display::None,
+                };
+            }
+            LedgerButton::Export => {
+                ***export_display = match storage::write(LEDGER_FILE, &ledger.to_csv()) {
+                    Ok(()) => format!("saved {LEDGER_FILE}"),
+                    Err(e) => format!("export failed: {e}"),
+                };
+            }
+        }
+    }
+}
+
+fn update_ledger_panel(
+    mut commands: Commands,
+    ledger: Res<Ledger>,
+    panel: Single<Ref<Node>, With<LedgerPanel>>,
+    mut stats_display: Single<&mut Text, With<LedgerStatsDisplay>>,
+    chart: Single<Entity, With<LedgerChart>>,
+) {
+    if panel.display == Display::None || !(ledger.is_changed() || panel.is_changed()) {
+        return;
+    }
+    ***stats_display = ledger.summary();
+
+    commands.entity(*chart).despawn_children();
+    let points = ledger.chart_points();
+    let (Some(&min), Some(&max)) = (points.iter().min(), points.iter().max()) else {
+        return;
+    };
+    let range = (max - min).max(1) as f32;
+    let step = CHART_WIDTH / (points.len().max(2) - 1) as f32;
+    let positions: Vec<Vec2> = points
+        .iter()
+        .enumerate()
+        .map(|(i, &score)| {
+            Vec2::new(
+                i as f32 * step,
+                CHART_HEIGHT - (score - min) as f32 / range * CHART_HEIGHT,
+            )
+        })
+        .collect();
+    for segment in positions.windows(2) {
+        let delta = segment[1] - segment[0];
+        let center = (segment[0] + segment[1]) / 2.;
+        let length = delta.length();
+        commands.entity(*chart).with_child((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(center.x - length / 2.),
+                top: Val::Px(center.y - 1.),
+                width: Val::Px(length),
+                height: Val::Px(2.),
+                ..default()
+            },
+            UiTransform {
+                rotation: Rot2::radians(delta.to_angle()),
+                ..default()
+            },
+            BackgroundColor(if segment[1].y <= segment[0].y {
+                Color::srgb_u8(0, 200, 64)
+            } else {
+                Color::srgb_u8(220, 48, 48)
+            }),
+        ));
+    }
+}
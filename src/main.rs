@@ -2,16 +2,39 @@ use bevy::{asset::AssetMetaCheck, prelude::*};
 use bevy_asset_loader::prelude::*;
 use bevy_light_2d::prelude::*;
 use bevy_rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
+mod animation;
+mod args;
 mod assets;
 mod cake;
+mod calendar;
+mod casino;
+mod credit;
+mod economy;
+mod fair;
 mod gamba;
+mod ledger;
+mod particles;
+mod recipient;
+mod replay;
+mod rules;
+mod safeguards;
+mod save;
+mod storage;
+mod trick_candles;
+mod tween;
 mod ui;
 mod util;
+mod wish;
 
 use assets::Assets;
 
-use crate::{cake::CakePlugin, gamba::GambaPlugin, ui::UiPlugin};
+use crate::{
+    animation::AnimationPlugin, cake::CakePlugin, casino::CasinoPlugin, credit::CreditPlugin,
+    fair::FairPlugin, gamba::GambaPlugin, ledger::LedgerPlugin, replay::ReplayPlugin,
+    safeguards::SafeguardsPlugin, save::SavePlugin, tween::TweenPlugin, ui::UiPlugin,
+};
 
 fn main() {
     App::new()
@@ -32,8 +55,17 @@ fn main() {
                 }),
             Light2dPlugin,
             EntropyPlugin::<WyRand>::default(),
+            ReplayPlugin,
+            AnimationPlugin,
+            TweenPlugin,
             CakePlugin,
             GambaPlugin,
+            CasinoPlugin,
+            CreditPlugin,
+            FairPlugin,
+            LedgerPlugin,
+            SafeguardsPlugin,
+            SavePlugin,
             UiPlugin,
         ))
         .init_state::<GameState>()
@@ -55,10 +87,12 @@ fn setup_camera(mut commands: Commands) {
     ));
 }
 
-#[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
+#[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States, Serialize, Deserialize)]
 pub enum GameState {
     #[default]
     AssetLoading,
     Cake,
+    Calendar,
     Gamba,
+    Casino,
 }
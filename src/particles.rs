@@ -0,0 +1,450 @@
+use bevy::prelude::*;
+use bevy_rand::prelude::*;
+use rand_core::{RngCore, SeedableRng};
+
+use crate::assets::Assets;
+
+/// Simple CPU particles, drawn as sprites. Particles are children of their [`ParticleEmitter`],
+/// so they go away with it.
+///
+/// Both [`crate::cake::CakePlugin`] and [`crate::gamba::GambaPlugin`] add it if it isn't there yet.
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (emit_particles, update_particles).chain());
+    }
+}
+
+/// Spawns particles where the entity is. Ranges are `(min, max)` and picked from per particle.
+///
+/// The emitter despawns itself once it has stopped spawning and all of its particles are gone.
+#[derive(Component, Clone)]
+#[require(Transform, Visibility, EmitterState)]
+pub struct ParticleEmitter {
+    /// Particles spawned per second for as long as the emitter is running.
+    pub rate: f32,
+    /// Particles spawned at once when the emitter starts.
+    pub burst: u32,
+    /// Seconds the emitter keeps spawning for, `f32::INFINITY` to never stop.
+    pub duration: f32,
+    pub lifetime: (f32, f32),
+    pub velocity: (Vec2, Vec2),
+    pub gravity: (Vec2, Vec2),
+    /// Rotation speed in radians per second.
+    pub spin: (f32, f32),
+    pub scale: (f32, f32),
+    /// Colours a particle starts with, one picked per particle.
+    pub colors: Vec<Color>,
+    /// Colour particles turn into by the end of their life, `None` to keep their own.
+    pub end_color: Option<Color>,
+    /// Alpha at the start and the end of a particle's life, multiplied with its colour.
+    pub alpha: (f32, f32),
+    pub look: ParticleLook,
+}
+
+#[derive(Clone)]
+pub enum ParticleLook {
+    /// A plain square, this many pixels wide.
+    Square(f32),
+    /// Frames `first..first + frames` of [`Assets::textures`], either played once over each
+    /// particle's life or, if not `animated`, one of them picked per particle.
+    Atlas {
+        first: usize,
+        frames: usize,
+        animated: bool,
+    },
+}
+
+/// Where an emitter is at. Its RNG is seeded from the entity rather than the global RNG, so
+/// effects don't change the outcome of anything else.
+#[derive(Component, Default)]
+struct EmitterState {
+    elapsed: f32,
+    /// Particles owed by the spawn rate that didn't add up to a whole one yet.
+    pending: f32,
+    burst_done: bool,
+    rng: Option<WyRand>,
+}
+
+#[derive(Component)]
+pub struct Particle {
+    age: f32,
+    lifetime: f32,
+    velocity: Vec2,
+    gravity: Vec2,
+    spin: f32,
+    scale: f32,
+    color: Color,
+    end_color: Color,
+    alpha: (f32, f32),
+    frames: ParticleFrames,
+}
+
+#[derive(Clone, Copy)]
+enum ParticleFrames {
+    None,
+    Fixed(usize),
+    Animated { first: usize, frames: usize },
+}
+
+impl ParticleEmitter {
+    /// Crumbs flying off where the cake was bitten.
+    pub fn crumbs() -> Self {
+        Self {
+            rate: 0.,
+            burst: 12,
+            duration: 0.,
+            lifetime: (0.4, 0.8),
+            velocity: (Vec2::new(-150., 50.), Vec2::new(150., 250.)),
+            gravity: (Vec2::new(0., -900.), Vec2::new(0., -700.)),
+            spin: (-10., 10.),
+            scale: (0.6, 1.2),
+            colors: vec![
+                Color::srgb_u8(222, 184, 135),
+                Color::srgb_u8(196, 150, 110),
+                Color::srgb_u8(245, 222, 179),
+            ],
+            end_color: None,
+            alpha: (1., 0.),
+            look: ParticleLook::Square(6.),
+        }
+    }
+
+    /// Smoke rising from a candle that was just blown out.
+    pub fn smoke() -> Self {
+        Self {
+            rate: 12.,
+            burst: 0,
+            duration: 0.8,
+            lifetime: (0.8, 1.4),
+            velocity: (Vec2::new(-15., 40.), Vec2::new(15., 80.)),
+            gravity: (Vec2::new(-10., 5.), Vec2::new(10., 20.)),
+            spin: (-1., 1.),
+            scale: (1.5, 2.5),
+            colors: vec![Color::WHITE],
+            end_color: Some(Color::srgb_u8(120, 120, 120)),
+            alpha: (0.8, 0.),
+            look: ParticleLook::Atlas {
+                first: 40,
+                frames: 5,
+                animated: true,
+            },
+        }
+    }
+
+    /// Confetti bursting out and fluttering down.
+    pub fn confetti() -> Self {
+        Self {
+            rate: 0.,
+            burst: 80,
+            duration: 0.,
+            lifetime: (2., 3.5),
+            velocity: (Vec2::new(-350., -100.), Vec2::new(350., 250.)),
+            gravity: (Vec2::new(-30., -350.), Vec2::new(30., -250.)),
+            spin: (-8., 8.),
+            scale: (0.8, 1.2),
+            colors: vec![
+                Color::srgb(0.95, 0.81, 0.78),
+                Color::srgb(0.98, 0.85, 0.3),
+                Color::srgb(0.5, 0.8, 0.95),
+                Color::srgb(0.6, 0.9, 0.55),
+                Color::srgb(0.85, 0.55, 0.9),
+            ],
+            end_color: None,
+            alpha: (1., 0.),
+            look: ParticleLook::Square(8.),
+        }
+    }
+
+    /// Coins showering out of a win.
+    pub fn coins() -> Self {
+        Self {
+            rate: 0.,
+            burst: 25,
+            duration: 0.,
+            lifetime: (1., 1.6),
+            velocity: (Vec2::new(-200., 300.), Vec2::new(200., 600.)),
+            gravity: (Vec2::new(0., -1300.), Vec2::new(0., -1100.)),
+            spin: (-6., 6.),
+            scale: (0.8, 1.2),
+            colors: vec![Color::srgb_u8(255, 215, 0), Color::srgb_u8(255, 236, 139)],
+            end_color: None,
+            alpha: (1., 0.),
+            look: ParticleLook::Square(10.),
+        }
+    }
+
+    /// Advances the emitter by `dt` seconds and returns how many particles to spawn.
+    fn emit(&self, state: &mut EmitterState, dt: f32) -> u32 {
+        let mut count = 0;
+        if !state.burst_done {
+            state.burst_done = true;
+            count += self.burst;
+        }
+        if state.elapsed < self.duration {
+            state.pending += self.rate * dt.min(self.duration - state.elapsed);
+            count += state.pending as u32;
+            state.pending = state.pending.fract();
+        }
+        state.elapsed += dt;
+        count
+    }
+
+    fn finished(&self, state: &EmitterState) -> bool {
+        state.burst_done && state.elapsed >= self.duration
+    }
+
+    /// Rolls a new particle.
+    fn particle(&self, rng: &mut WyRand) -> Particle {
+        let color = if self.colors.is_empty() {
+            Color::WHITE
+        } else {
+            self.colors[rng.next_u32() as usize % self.colors.len()]
+        };
+        let frames = match &self.look {
+            ParticleLook::Square(_) => ParticleFrames::None,
+            ParticleLook::Atlas {
+                first,
+                frames,
+                animated: true,
+            } => ParticleFrames::Animated {
+                first: *first,
+                frames: (*frames).max(1),
+            },
+            ParticleLook::Atlas { first, frames, .. } => {
+                ParticleFrames::Fixed(first + rng.next_u32() as usize % (*frames).max(1))
+            }
+        };
+        Particle {
+            age: 0.,
+            lifetime: between(rng, self.lifetime.0, self.lifetime.1).max(f32::EPSILON),
+            velocity: between_vec2(rng, self.velocity),
+            gravity: between_vec2(rng, self.gravity),
+            spin: between(rng, self.spin.0, self.spin.1),
+            scale: between(rng, self.scale.0, self.scale.1),
+            color,
+            end_color: self.end_color.unwrap_or(color),
+            alpha: self.alpha,
+            frames,
+        }
+    }
+}
+
+impl Particle {
+    /// Moves the particle on by `dt` seconds and returns how far it travelled, or `None` once it
+    /// has lived out its lifetime.
+    pub fn step(&mut self, dt: f32) -> Option<Vec2> {
+        self.age += dt;
+        if self.age >= self.lifetime {
+            return None;
+        }
+        self.velocity += self.gravity * dt;
+        Some(self.velocity * dt)
+    }
+
+    /// How far through its life the particle is, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        (self.age / self.lifetime).min(1.)
+    }
+
+    pub fn color(&self) -> Color {
+        let t = self.progress();
+        let color = self.color.mix(&self.end_color, t);
+        color.with_alpha(color.alpha() * self.alpha.0.lerp(self.alpha.1, t))
+    }
+
+    /// Atlas frame to show, `None` for plain squares.
+    pub fn frame(&self) -> Option<usize> {
+        match self.frames {
+            ParticleFrames::None => None,
+            ParticleFrames::Fixed(frame) => Some(frame),
+            ParticleFrames::Animated { first, frames } => {
+                Some(first + ((self.progress() * frames as f32) as usize).min(frames - 1))
+            }
+        }
+    }
+}
+
+fn between(rng: &mut WyRand, min: f32, max: f32) -> f32 {
+    min + (max - min) * (rng.next_u32() as f32 / u32::MAX as f32)
+}
+
+fn between_vec2(rng: &mut WyRand, (min, max): (Vec2, Vec2)) -> Vec2 {
+    Vec2::new(between(rng, min.x, max.x), between(rng, min.y, max.y))
+}
+
+fn emit_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<Assets>,
+    mut emitters: Query<(
+        Entity,
+        &ParticleEmitter,
+        &mut EmitterState,
+        Option<&Children>,
+    )>,
+) {
+    for (entity, emitter, mut state, children) in &mut emitters {
+        let count = emitter.emit(&mut state, time.delta_secs());
+        if count == 0
+            && emitter.finished(&state)
+            && children.is_none_or(|children| children.is_empty())
+        {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let size = match &emitter.look {
+            ParticleLook::Square(size) => *size,
+            _ => 1.,
+        };
+        let rng = state
+            .rng
+            .get_or_insert_with(|| WyRand::seed_from_u64(entity.to_bits()));
+        for _ in 0..count {
+            let particle = emitter.particle(rng);
+            let sprite = match particle.frame() {
+                Some(frame) => Sprite {
+                    color: particle.color(),
+                    ..Sprite::from_atlas_image(
+                        assets.textures.clone(),
+                        TextureAtlas::from(assets.texture_atlas.clone()).with_index(frame),
+                    )
+                },
+                None => Sprite::from_color(particle.color(), Vec2::splat(size)),
+            };
+            commands.spawn((
+                sprite,
+                Transform::from_scale(Vec3::splat(particle.scale)),
+                particle,
+                ChildOf(entity),
+            ));
+        }
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut particle, mut transform, mut sprite) in &mut particles {
+        let Some(moved) = particle.step(dt) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        transform.translation += moved.extend(0.);
+        transform.rotate_z(particle.spin * dt);
+        sprite.color = particle.color();
+        if let (Some(frame), Some(atlas)) = (particle.frame(), &mut sprite.texture_atlas) {
+            atlas.index = frame;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitter(rate: f32, burst: u32, duration: f32) -> ParticleEmitter {
+        ParticleEmitter {
+            rate,
+            burst,
+            duration,
+            ..ParticleEmitter::crumbs()
+        }
+    }
+
+    fn particle(frames: ParticleFrames) -> Particle {
+        Particle {
+            age: 0.,
+            lifetime: 1.,
+            velocity: Vec2::new(10., 0.),
+            gravity: Vec2::new(0., -10.),
+            spin: 0.,
+            scale: 1.,
+            color: Color::WHITE,
+            end_color: Color::BLACK,
+            alpha: (1., 0.),
+            frames,
+        }
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{a} != {b}");
+    }
+
+    #[test]
+    fn burst_is_emitted_once() {
+        let emitter = emitter(0., 12, 0.);
+        let mut state = EmitterState::default();
+        assert_eq!(emitter.emit(&mut state, 0.1), 12);
+        assert!(emitter.finished(&state));
+        assert_eq!(emitter.emit(&mut state, 0.1), 0);
+    }
+
+    #[test]
+    fn rate_carries_fractions_over_to_later_frames() {
+        let emitter = emitter(10., 0, 1.);
+        let mut state = EmitterState::default();
+        let counts: Vec<u32> = (0..4).map(|_| emitter.emit(&mut state, 0.25)).collect();
+        assert_eq!(counts, [2, 3, 2, 3]);
+    }
+
+    #[test]
+    fn emitter_stops_after_its_duration() {
+        let emitter = emitter(10., 0, 1.);
+        let mut state = EmitterState::default();
+        assert_eq!(emitter.emit(&mut state, 0.5), 5);
+        assert!(!emitter.finished(&state));
+        // Only the part of the frame before the end counts.
+        assert_eq!(emitter.emit(&mut state, 2.), 5);
+        assert!(emitter.finished(&state));
+        assert_eq!(emitter.emit(&mut state, 1.), 0);
+    }
+
+    #[test]
+    fn step_applies_gravity() {
+        let mut particle = particle(ParticleFrames::None);
+        assert_eq!(particle.step(0.5), Some(Vec2::new(5., -2.5)));
+        assert_eq!(particle.velocity, Vec2::new(10., -5.));
+    }
+
+    #[test]
+    fn particle_dies_at_its_lifetime() {
+        let mut particle = particle(ParticleFrames::None);
+        assert!(particle.step(0.5).is_some());
+        assert_eq!(particle.step(0.5), None);
+    }
+
+    #[test]
+    fn color_fades_over_life() {
+        let mut particle = particle(ParticleFrames::None);
+        for (age, channel, alpha) in [(0., 1., 1.), (0.5, 0.5, 0.5), (1., 0., 0.), (2., 0., 0.)] {
+            particle.age = age;
+            let color = particle.color().to_linear();
+            assert_close(color.red, channel);
+            assert_close(color.green, channel);
+            assert_close(color.blue, channel);
+            assert_close(color.alpha, alpha);
+        }
+    }
+
+    #[test]
+    fn frames_play_once_over_life() {
+        let mut particle = particle(ParticleFrames::Animated {
+            first: 40,
+            frames: 5,
+        });
+        for (age, frame) in [(0., 40), (0.5, 42), (0.99, 44), (2., 44)] {
+            particle.age = age;
+            assert_eq!(particle.frame(), Some(frame));
+        }
+        particle.frames = ParticleFrames::Fixed(7);
+        assert_eq!(particle.frame(), Some(7));
+        particle.frames = ParticleFrames::None;
+        assert_eq!(particle.frame(), None);
+    }
+}
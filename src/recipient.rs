@@ -0,0 +1,53 @@
+//! Who the cake is for. The bundled `recipient.ron` sets the defaults, which launch options
+//! (`--name`, `--greeting`, `--color`, `--age`, or the same URL query parameters on the web)
+//! override.
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::args;
+
+/// Compiled in rather than loaded as an asset so the age is known before the cake is set up.
+const BUNDLED_CONFIG: &str = include_str!("../assets/recipient.ron");
+/// Oldest age a cake gets candles for, anything above is taken as a typo.
+pub const MAX_AGE: usize = 150;
+
+#[derive(Resource, Deserialize)]
+pub struct Recipient {
+    pub name: String,
+    /// Banner text, with `{name}` replaced by the recipient's name.
+    pub greeting: String,
+    /// Banner text colour as a hex code.
+    pub color: String,
+    pub age: usize,
+}
+
+impl Recipient {
+    pub fn from_config() -> Self {
+        let mut recipient: Recipient =
+            ron::from_str(BUNDLED_CONFIG).expect("bundled recipient.ron should parse");
+        if let Some(name) = args::get("name") {
+            recipient.name = name;
+        }
+        if let Some(greeting) = args::get("greeting") {
+            recipient.greeting = greeting;
+        }
+        if let Some(color) = args::get("color") {
+            recipient.color = color;
+        }
+        if let Some(age) = args::parse("age") {
+            recipient.age = age;
+        }
+        recipient.age = recipient.age.min(MAX_AGE);
+        recipient
+    }
+
+    pub fn banner(&self) -> String {
+        self.greeting.replace("{name}", &self.name)
+    }
+
+    /// The banner colour, or the usual pink if the hex code doesn't parse.
+    pub fn color(&self) -> Color {
+        Srgba::hex(&self.color).map_or(Color::srgb_u8(243, 207, 198), Color::from)
+    }
+}
@@ -0,0 +1,542 @@
+use bevy::{diagnostic::FrameCount, ecs::system::SystemParam, prelude::*};
+use bevy_rand::prelude::*;
+use rand_core::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState, args,
+    cake::{Age, BiteCakeMessage, ExtinguishFlameMessage},
+    calendar::Today,
+    casino::{CasinoBetMessage, CasinoRound, CasinoState, round_open},
+    credit::{Credit, CreditMessage},
+    economy::{EconomyMessage, Pantry, Score},
+    fair::{FairMessage, ProvablyFair},
+    gamba::{
+        Bet, BetChangeMessage, BetMessage, GambaRoundState, SugarCaneHeight, betting_open,
+        fishing::{FishingLog, FishingMessage},
+        income::{Income, IncomeMessage},
+        market::{Market, MarketMessage},
+    },
+    recipient::MAX_AGE,
+    safeguards::{SafeguardMessage, SafeguardSettings},
+    storage,
+    trick_candles::TrickCandleMessage,
+    wish::WishMessage,
+};
+
+/// Seeds the global RNG and records every player input so a session can be played back.
+///
+/// `--seed N` picks the RNG seed, `--record [NAME]` records the session and `--replay NAME`
+/// plays a stored recording back instead of taking player input.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let replay = args::get("replay").and_then(|name| load_recording(&name));
+        let seed = match &replay {
+            Some(replay) => Some(replay.recording.seed),
+            None => args::parse("seed"),
+        };
+        if let Some(replay) = replay {
+            app.insert_resource(replay);
+        } else if args::has("record") {
+            app.insert_resource(Recorder {
+                name: args::get("record")
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| DEFAULT_RECORDING.into()),
+                recording: Recording::default(),
+                dirty: false,
+                flush: Timer::from_seconds(RECORDING_FLUSH_SECS, TimerMode::Repeating),
+            });
+        }
+
+        app.insert_resource(SessionSeed(seed))
+            .add_systems(PreStartup, seed_rng)
+            .add_systems(
+                Startup,
+                spawn_replay_banner.run_if(resource_exists::<Replay>),
+            )
+            .add_systems(OnExit(GameState::AssetLoading), start_clock)
+            .add_systems(
+                PreUpdate,
+                play_recording
+                    .run_if(resource_exists::<Replay>.and(resource_exists::<ReplayClock>)),
+            )
+            .add_systems(
+                Last,
+                (
+                    (record_inputs, write_recording)
+                        .chain()
+                        .run_if(resource_exists::<Recorder>),
+                    check_divergence.run_if(resource_exists::<Replay>),
+                )
+                    .run_if(resource_exists::<ReplayClock>),
+            );
+    }
+}
+
+const DEFAULT_RECORDING: &str = "recording.json";
+/// How often a recording in progress is written out, besides when the game exits.
+const RECORDING_FLUSH_SECS: f32 = 10.;
+
+/// Seed of the global RNG, or `None` to draw one from entropy.
+#[derive(Resource)]
+struct SessionSeed(Option<u64>);
+
+/// Frame at which the recorded part of the session starts, right after asset loading.
+#[derive(Resource)]
+struct ReplayClock {
+    start: u32,
+}
+
+impl ReplayClock {
+    fn frame(&self, frame_count: &FrameCount) -> u32 {
+        frame_count.0.wrapping_sub(self.start)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+enum ReplayEvent {
+    Bet(BetMessage),
+    BetChange(BetChangeMessage),
+    BiteCake,
+    ExtinguishFlame { slot: usize },
+    Wish,
+    Fair(FairMessage),
+    Credit(CreditMessage),
+    CasinoBet(CasinoBetMessage),
+    Fishing(FishingMessage),
+    Market(MarketMessage),
+    Income(IncomeMessage),
+    Economy(EconomyMessage),
+    TrickCandles(TrickCandleMessage),
+    Safeguard(SafeguardMessage),
+    State(GameState),
+    CasinoState(CasinoState),
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedEvent {
+    frame: u32,
+    event: ReplayEvent,
+}
+
+/// Score after every change, used to notice when a replay stops matching its recording.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    frame: u32,
+    score: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Recording {
+    seed: u64,
+    score: u64,
+    bet: u64,
+    age: usize,
+    #[serde(default)]
+    credit: Credit,
+    #[serde(default)]
+    safeguards: SafeguardSettings,
+    #[serde(default)]
+    fishing: FishingLog,
+    #[serde(default)]
+    canes: SugarCaneHeight,
+    #[serde(default)]
+    market: Market,
+    #[serde(default)]
+    income: Income,
+    #[serde(default)]
+    pantry: Pantry,
+    #[serde(default)]
+    today: Today,
+    /// Provably fair server seeds in the order they were drawn, they don't come from the RNG.
+    #[serde(default)]
+    server_seeds: Vec<[u8; 32]>,
+    events: Vec<RecordedEvent>,
+    checkpoints: Vec<Checkpoint>,
+}
+
+#[derive(Resource)]
+struct Recorder {
+    name: String,
+    recording: Recording,
+    /// Whether the recording changed since it was last written.
+    dirty: bool,
+    flush: Timer,
+}
+
+/// Present while a recording is being played back.
+#[derive(Resource)]
+pub struct Replay {
+    recording: Recording,
+    next_event: usize,
+    next_checkpoint: usize,
+    diverged: bool,
+}
+
+/// Run condition that is false while a replay drives the game instead of the player.
+pub fn live_input(replay: Option<Res<Replay>>) -> bool {
+    replay.is_none()
+}
+
+fn load_recording(name: &str) -> Option<Replay> {
+    let Some(contents) = storage::read(name) else {
+        warn!("No recording named {name}");
+        return None;
+    };
+    match serde_json::from_str(&contents) {
+        Ok(recording) => Some(Replay {
+            recording,
+            next_event: 0,
+            next_checkpoint: 0,
+            diverged: false,
+        }),
+        Err(e) => {
+            warn!("Ignoring recording {name}: {e}");
+            None
+        }
+    }
+}
+
+fn seed_rng(
+    session_seed: Res<SessionSeed>,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    recorder: Option<ResMut<Recorder>>,
+) {
+    let seed = session_seed.0.unwrap_or_else(|| rng.next_u64());
+    **rng = WyRand::seed_from_u64(seed);
+    info!("Session seed: {seed}");
+    if let Some(mut recorder) = recorder {
+        recorder.recording.seed = seed;
+    }
+}
+
+fn start_clock(
+    mut commands: Commands,
+    frame_count: Res<FrameCount>,
+    replay: Option<Res<Replay>>,
+    recorder: Option<ResMut<Recorder>>,
+    score: Res<Score>,
+    bet: Res<Bet>,
+    age: Res<Age>,
+    credit: Res<Credit>,
+    safeguards: Res<SafeguardSettings>,
+    fishing: Res<FishingLog>,
+    canes: Res<SugarCaneHeight>,
+    market: Res<Market>,
+    income: Res<Income>,
+    pantry: Res<Pantry>,
+    today: Res<Today>,
+    mut fair: ResMut<ProvablyFair>,
+) {
+    commands.insert_resource(ReplayClock {
+        start: frame_count.0,
+    });
+    if let Some(replay) = replay {
+        commands.insert_resource(Score(replay.recording.score));
+        commands.insert_resource(Bet(replay.recording.bet));
+        commands.insert_resource(Age(replay.recording.age.min(MAX_AGE)));
+        commands.insert_resource(replay.recording.credit.clone());
+        commands.insert_resource(replay.recording.safeguards.clone());
+        commands.insert_resource(replay.recording.fishing.clone());
+        commands.insert_resource(replay.recording.canes.clone());
+        commands.insert_resource(replay.recording.market.clone());
+        commands.insert_resource(replay.recording.income.clone());
+        commands.insert_resource(replay.recording.pantry.clone());
+        commands.insert_resource(replay.recording.today);
+        fair.replay_seeds(replay.recording.server_seeds.clone());
+    }
+    if let Some(mut recorder) = recorder {
+        recorder.recording.score = score.0;
+        recorder.recording.bet = bet.0;
+        recorder.recording.age = age.0;
+        recorder.recording.credit = credit.clone();
+        recorder.recording.safeguards = safeguards.clone();
+        recorder.recording.fishing = fishing.clone();
+        recorder.recording.canes = canes.clone();
+        recorder.recording.market = market.clone();
+        recorder.recording.income = income.clone();
+        recorder.recording.pantry = pantry.clone();
+        recorder.recording.today = *today;
+        recorder.dirty = true;
+    }
+}
+
+/// Writes every kind of message a recording holds.
+#[derive(SystemParam)]
+struct ReplayWriters<'w> {
+    bet: MessageWriter<'w, BetMessage>,
+    bet_change: MessageWriter<'w, BetChangeMessage>,
+    bite: MessageWriter<'w, BiteCakeMessage>,
+    extinguish: MessageWriter<'w, ExtinguishFlameMessage>,
+    wish: MessageWriter<'w, WishMessage>,
+    fair: MessageWriter<'w, FairMessage>,
+    credit: MessageWriter<'w, CreditMessage>,
+    casino_bet: MessageWriter<'w, CasinoBetMessage>,
+    fishing: MessageWriter<'w, FishingMessage>,
+    market: MessageWriter<'w, MarketMessage>,
+    income: MessageWriter<'w, IncomeMessage>,
+    economy: MessageWriter<'w, EconomyMessage>,
+    trick_candle: MessageWriter<'w, TrickCandleMessage>,
+    safeguard: MessageWriter<'w, SafeguardMessage>,
+}
+
+/// Reads every kind of message a recording holds.
+#[derive(SystemParam)]
+struct ReplayReaders<'w, 's> {
+    bet: MessageReader<'w, 's, BetMessage>,
+    bet_change: MessageReader<'w, 's, BetChangeMessage>,
+    bite: MessageReader<'w, 's, BiteCakeMessage>,
+    extinguish: MessageReader<'w, 's, ExtinguishFlameMessage>,
+    wish: MessageReader<'w, 's, WishMessage>,
+    fair: MessageReader<'w, 's, FairMessage>,
+    credit: MessageReader<'w, 's, CreditMessage>,
+    casino_bet: MessageReader<'w, 's, CasinoBetMessage>,
+    fishing: MessageReader<'w, 's, FishingMessage>,
+    market: MessageReader<'w, 's, MarketMessage>,
+    income: MessageReader<'w, 's, IncomeMessage>,
+    economy: MessageReader<'w, 's, EconomyMessage>,
+    trick_candle: MessageReader<'w, 's, TrickCandleMessage>,
+    safeguard: MessageReader<'w, 's, SafeguardMessage>,
+}
+
+fn play_recording(
+    mut replay: ResMut<Replay>,
+    clock: Res<ReplayClock>,
+    frame_count: Res<FrameCount>,
+    mut writers: ReplayWriters,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut casino_state: ResMut<NextState<CasinoState>>,
+    round_state: Option<Res<State<GambaRoundState>>>,
+    casino_round: Option<Res<CasinoRound>>,
+) {
+    let frame = clock.frame(&frame_count);
+    let betting = betting_open(round_state);
+    let casino_betting = round_open(casino_round);
+    while let Some(recorded) = replay.recording.events.get(replay.next_event) {
+        if recorded.frame > frame {
+            break;
+        }
+        // Reveals take real time, so a bet may come up before this run's round is over. Hold it
+        // and everything after it back until betting opens again.
+        if matches!(recorded.event, ReplayEvent::Bet(_)) && !betting
+            || matches!(recorded.event, ReplayEvent::CasinoBet(_)) && !casino_betting
+        {
+            break;
+        }
+        match recorded.event.clone() {
+            ReplayEvent::Bet(msg) => {
+                writers.bet.write(msg);
+            }
+            ReplayEvent::BetChange(msg) => {
+                writers.bet_change.write(msg);
+            }
+            ReplayEvent::BiteCake => {
+                writers.bite.write(BiteCakeMessage);
+            }
+            ReplayEvent::ExtinguishFlame { slot } => {
+                writers.extinguish.write(ExtinguishFlameMessage { slot });
+            }
+            ReplayEvent::Fair(msg) => {
+                writers.fair.write(msg);
+            }
+            ReplayEvent::Credit(msg) => {
+                writers.credit.write(msg);
+            }
+            ReplayEvent::CasinoBet(msg) => {
+                writers.casino_bet.write(msg);
+            }
+            ReplayEvent::Fishing(msg) => {
+                writers.fishing.write(msg);
+            }
+            ReplayEvent::Market(msg) => {
+                writers.market.write(msg);
+            }
+            ReplayEvent::Income(msg) => {
+                writers.income.write(msg);
+            }
+            ReplayEvent::Economy(msg) => {
+                writers.economy.write(msg);
+            }
+            ReplayEvent::Wish => {
+                writers.wish.write(WishMessage);
+            }
+            ReplayEvent::TrickCandles(msg) => {
+                writers.trick_candle.write(msg);
+            }
+            ReplayEvent::Safeguard(msg) => {
+                writers.safeguard.write(msg);
+            }
+            ReplayEvent::State(state) => game_state.set(state),
+            ReplayEvent::CasinoState(state) => casino_state.set(state),
+        }
+        replay.next_event += 1;
+        if replay.next_event == replay.recording.events.len() {
+            info!("Replay finished at frame {frame}");
+        }
+    }
+}
+
+fn record_inputs(
+    mut recorder: ResMut<Recorder>,
+    clock: Res<ReplayClock>,
+    frame_count: Res<FrameCount>,
+    mut readers: ReplayReaders,
+    next_state: Res<NextState<GameState>>,
+    next_casino_state: Res<NextState<CasinoState>>,
+    score: Res<Score>,
+    fair: Res<ProvablyFair>,
+) {
+    let frame = clock.frame(&frame_count);
+    let mut events: Vec<ReplayEvent> = Vec::new();
+    events.extend(readers.bet.read().cloned().map(ReplayEvent::Bet));
+    events.extend(
+        readers
+            .bet_change
+            .read()
+            .cloned()
+            .map(ReplayEvent::BetChange),
+    );
+    events.extend(readers.bite.read().map(|_| ReplayEvent::BiteCake));
+    events.extend(
+        readers
+            .extinguish
+            .read()
+            .map(|msg| ReplayEvent::ExtinguishFlame { slot: msg.slot }),
+    );
+    events.extend(readers.wish.read().map(|_| ReplayEvent::Wish));
+    events.extend(readers.fair.read().cloned().map(ReplayEvent::Fair));
+    events.extend(readers.credit.read().cloned().map(ReplayEvent::Credit));
+    events.extend(
+        readers
+            .casino_bet
+            .read()
+            .cloned()
+            .map(ReplayEvent::CasinoBet),
+    );
+    events.extend(readers.fishing.read().cloned().map(ReplayEvent::Fishing));
+    events.extend(readers.market.read().cloned().map(ReplayEvent::Market));
+    events.extend(readers.income.read().cloned().map(ReplayEvent::Income));
+    events.extend(readers.economy.read().cloned().map(ReplayEvent::Economy));
+    events.extend(
+        readers
+            .trick_candle
+            .read()
+            .cloned()
+            .map(ReplayEvent::TrickCandles),
+    );
+    events.extend(
+        readers
+            .safeguard
+            .read()
+            .cloned()
+            .map(ReplayEvent::Safeguard),
+    );
+    if let NextState::Pending(state) = &*next_state {
+        events.push(ReplayEvent::State(state.clone()));
+    }
+    if let NextState::Pending(state) = &*next_casino_state {
+        events.push(ReplayEvent::CasinoState(state.clone()));
+    }
+
+    let recording = &mut recorder.recording;
+    let mut dirty = !events.is_empty();
+    recording.events.extend(
+        events
+            .into_iter()
+            .map(|event| RecordedEvent { frame, event }),
+    );
+    if score.is_changed() {
+        recording.checkpoints.push(Checkpoint {
+            frame,
+            score: score.0,
+        });
+        dirty = true;
+    }
+
+    if fair.drawn_seeds().len() != recording.server_seeds.len() {
+        recording.server_seeds = fair.drawn_seeds().to_vec();
+        dirty = true;
+    }
+    recorder.dirty |= dirty;
+}
+
+/// Writes the recording out every [`RECORDING_FLUSH_SECS`] and when the game exits, rather than
+/// after every event.
+fn write_recording(
+    time: Res<Time<Real>>,
+    mut exit_messages: MessageReader<AppExit>,
+    mut recorder: ResMut<Recorder>,
+) {
+    recorder.flush.tick(time.delta());
+    let exiting = exit_messages.read().next().is_some();
+    if !recorder.dirty || !(exiting || recorder.flush.just_finished()) {
+        return;
+    }
+    recorder.dirty = false;
+    match serde_json::to_string(&recorder.recording) {
+        Ok(contents) => {
+            if let Err(e) = storage::write(&recorder.name, &contents) {
+                warn!("Failed to write recording: {e}");
+            }
+        }
+        Err(e) => warn!("Failed to serialize recording: {e}"),
+    }
+}
+
+fn check_divergence(
+    mut replay: ResMut<Replay>,
+    clock: Res<ReplayClock>,
+    frame_count: Res<FrameCount>,
+    score: Res<Score>,
+) {
+    if !score.is_changed() {
+        return;
+    }
+    let frame = clock.frame(&frame_count);
+    let expected = replay
+        .recording
+        .checkpoints
+        .get(replay.next_checkpoint)
+        .map(|checkpoint| (checkpoint.frame, checkpoint.score));
+    replay.next_checkpoint += 1;
+    if replay.diverged {
+        return;
+    }
+    match expected {
+        Some((_, expected_score)) if expected_score == score.0 => (),
+        Some((expected_frame, expected_score)) => {
+            replay.diverged = true;
+            warn!(
+                "Replay diverged at frame {frame}: score is ${} but the recording had ${expected_score} at frame {expected_frame}",
+                score.0
+            );
+        }
+        None => {
+            replay.diverged = true;
+            warn!("Replay diverged at frame {frame}: score changed after the recording ended");
+        }
+    }
+}
+
+fn spawn_replay_banner(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(0.),
+            right: Val::Px(0.),
+            padding: UiRect::all(Val::Px(8.)),
+            ..default()
+        },
+        GlobalZIndex(10),
+        children![(
+            Text::new("REPLAY"),
+            TextFont {
+                font_size: 24.,
+                ..default()
+            },
+            TextColor(Color::srgb_u8(255, 64, 64)),
+        )],
+    ));
+}
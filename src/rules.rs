@@ -0,0 +1,241 @@
+//! Bet resolution shared by the game and the `gamba-sim` binary.
+//!
+//! Nothing in here may depend on the rest of the crate, since the simulator includes this file
+//! on its own.
+
+use bevy::prelude::*;
+use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+
+/// How many sugar cane columns grow on each side of the sign.
+pub const SUGAR_CANE_COUNT: usize = 6;
+
+#[derive(Component, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Where the next sugar cane grows, which decides every kind of bet.
+#[derive(Clone, PartialEq)]
+pub struct Outcome {
+    pub side: Side,
+    pub column: usize,
+}
+
+impl Outcome {
+    /// Maps a uniform roll to one of the `2 * SUGAR_CANE_COUNT` columns. The side only depends
+    /// on the parity of the roll.
+    pub fn from_roll(roll: u32) -> Self {
+        Self {
+            side: if roll.is_multiple_of(2) {
+                Side::Left
+            } else {
+                Side::Right
+            },
+            column: (roll / 2) as usize % SUGAR_CANE_COUNT,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum Parity {
+    Odd,
+    Even,
+}
+
+/// What a bet is placed on. Columns are numbered from 1 in labels.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum BetKind {
+    /// Either side of the sign, 1 in 2.
+    Side(Side),
+    /// One column on one side, 1 in 12.
+    Exact { side: Side, column: usize },
+    /// The same column on either side, 1 in 6.
+    Column(usize),
+    /// Two neighbouring columns on one side, 1 in 6.
+    AdjacentPair { side: Side, first: usize },
+    /// Every odd or every even column on both sides, 1 in 2.
+    Parity(Parity),
+}
+
+impl BetKind {
+    pub fn wins(&self, outcome: &Outcome) -> bool {
+        match self {
+            BetKind::Side(side) => *side == outcome.side,
+            BetKind::Exact { side, column } => *side == outcome.side && *column == outcome.column,
+            BetKind::Column(column) => *column == outcome.column,
+            BetKind::AdjacentPair { side, first } => {
+                *side == outcome.side && (*first..=*first + 1).contains(&outcome.column)
+            }
+            BetKind::Parity(parity) => {
+                // Columns 1, 3 and 5 are odd in labels but even as indices.
+                outcome.column.is_multiple_of(2) == (*parity == Parity::Odd)
+            }
+        }
+    }
+
+    pub fn payout(&self, odds: &OddsTable) -> u64 {
+        match self {
+            BetKind::Side(_) => odds.side,
+            BetKind::Exact { .. } => odds.exact,
+            BetKind::Column(_) => odds.column,
+            BetKind::AdjacentPair { .. } => odds.adjacent_pair,
+            BetKind::Parity(_) => odds.parity,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            BetKind::Side(side) => side_name(side).into(),
+            BetKind::Exact { side, column } => format!("{} {}", side_name(side), column + 1),
+            BetKind::Column(column) => format!("column {} on both sides", column + 1),
+            BetKind::AdjacentPair { side, first } => {
+                format!("{} {}-{}", side_name(side), first + 1, first + 2)
+            }
+            BetKind::Parity(Parity::Odd) => "odd columns".into(),
+            BetKind::Parity(Parity::Even) => "even columns".into(),
+        }
+    }
+}
+
+pub fn side_name(side: &Side) -> &'static str {
+    match side {
+        Side::Left => "LEFT",
+        Side::Right => "RIGHT",
+    }
+}
+
+/// Net payout of each bet kind as a multiple of the stake.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct OddsTable {
+    side: u64,
+    exact: u64,
+    column: u64,
+    adjacent_pair: u64,
+    parity: u64,
+}
+
+/// How a settled bet turned out.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Settlement {
+    Won,
+    Lost,
+    /// Lost everything, the score is either bailed out or left at 0.
+    Bankrupt,
+}
+
+/// Settles a bet on `outcome`, bailing the player out when they go broke if `free_bailout` is
+/// set and clamping the bet to what they have left.
+pub fn settle(
+    score: &mut u64,
+    bet: &mut u64,
+    kind: &BetKind,
+    outcome: &Outcome,
+    odds: &OddsTable,
+    free_bailout: bool,
+    rng: &mut impl RngCore,
+) -> Settlement {
+    if kind.wins(outcome) {
+        *score = score.saturating_add(bet.saturating_mul(kind.payout(odds)));
+        return Settlement::Won;
+    }
+    *score = score.saturating_sub(*bet);
+    let settlement = if *score == 0 {
+        if free_bailout {
+            *score = bailout(rng);
+        }
+        Settlement::Bankrupt
+    } else {
+        Settlement::Lost
+    };
+    *bet = (*bet).min(*score);
+    settlement
+}
+
+/// Money handed to a player who went broke, between 5 and 9 dollars.
+pub fn bailout(rng: &mut impl RngCore) -> u64 {
+    rng.next_u32() as u64 % 5 + 5
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_rand::prelude::WyRand;
+    use rand_core::SeedableRng;
+
+    use super::*;
+
+    const ODDS: OddsTable = OddsTable {
+        side: 1,
+        exact: 11,
+        column: 5,
+        adjacent_pair: 5,
+        parity: 0,
+    };
+    const OUTCOME: Outcome = Outcome {
+        side: Side::Left,
+        column: 2,
+    };
+
+    fn settle_bet(
+        score: u64,
+        bet: u64,
+        kind: BetKind,
+        free_bailout: bool,
+    ) -> (Settlement, u64, u64) {
+        let (mut score, mut bet) = (score, bet);
+        let mut rng = WyRand::seed_from_u64(0);
+        let settlement = settle(
+            &mut score,
+            &mut bet,
+            &kind,
+            &OUTCOME,
+            &ODDS,
+            free_bailout,
+            &mut rng,
+        );
+        (settlement, score, bet)
+    }
+
+    #[test]
+    fn win_pays_the_odds_on_top_of_the_stake() {
+        let exact = BetKind::Exact {
+            side: Side::Left,
+            column: 2,
+        };
+        assert_eq!(settle_bet(10, 3, exact, false), (Settlement::Won, 43, 3));
+    }
+
+    #[test]
+    fn loss_costs_the_stake() {
+        let side = BetKind::Side(Side::Right);
+        assert_eq!(settle_bet(10, 3, side, false), (Settlement::Lost, 7, 3));
+    }
+
+    #[test]
+    fn push_leaves_the_score_alone() {
+        // The outcome is column 3 in labels, and odd columns pay nothing on top of the stake.
+        let parity = BetKind::Parity(Parity::Odd);
+        assert_eq!(settle_bet(10, 3, parity, false), (Settlement::Won, 10, 3));
+    }
+
+    #[test]
+    fn losing_everything_is_bankruptcy() {
+        let side = BetKind::Side(Side::Right);
+        assert_eq!(
+            settle_bet(3, 3, side.clone(), false),
+            (Settlement::Bankrupt, 0, 0)
+        );
+
+        let (settlement, score, bet) = settle_bet(3, 3, side, true);
+        assert_eq!(settlement, Settlement::Bankrupt);
+        assert!((5..=9).contains(&score), "bailout of {score}");
+        assert_eq!(bet, 3);
+    }
+
+    #[test]
+    fn bet_is_clamped_to_what_is_left() {
+        let side = BetKind::Side(Side::Right);
+        assert_eq!(settle_bet(5, 4, side, false), (Settlement::Lost, 1, 1));
+    }
+}
@@ -0,0 +1,620 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState,
+    casino::CasinoRound,
+    gamba::BetResultMessage,
+    replay::live_input,
+    util::{cycle, despawn_all, unix_time},
+};
+
+/// Reality checks, session limits and the cool-down, for the gamba and the casino alike.
+pub struct SafeguardsPlugin;
+
+impl Plugin for SafeguardsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SafeguardSettings>()
+            .init_resource::<Session>()
+            .add_message::<SafeguardMessage>()
+            .add_systems(OnEnter(GameState::Gamba), setup_panel)
+            .add_systems(OnEnter(GameState::Casino), setup_panel)
+            .add_systems(
+                OnExit(GameState::Gamba),
+                (despawn_all::<OnBettingScreen>, resume),
+            )
+            .add_systems(
+                OnExit(GameState::Casino),
+                (despawn_all::<OnBettingScreen>, resume),
+            )
+            .add_systems(OnEnter(GameState::Cake), setup_cake)
+            .add_systems(OnExit(GameState::Cake), despawn_all::<OnCakeScreen>)
+            .add_systems(
+                Update,
+                (
+                    track_session,
+                    (reality_check, handle_safeguard_buttons).run_if(live_input),
+                    handle_safeguard_messages,
+                    update_safeguard_panel,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Gamba).or(in_state(GameState::Casino))),
+            )
+            .add_systems(
+                Update,
+                update_lockout_display.run_if(in_state(GameState::Cake)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct OnBettingScreen;
+
+#[derive(Component)]
+struct OnCakeScreen;
+
+const REALITY_CHECK_OPTIONS: [u32; 4] = [0, 15, 30, 60];
+const LOSS_LIMIT_OPTIONS: [u64; 5] = [0, 10, 50, 100, 500];
+const WAGER_CAP_OPTIONS: [u64; 5] = [0, 100, 500, 1000, 5000];
+const COOLDOWN_OPTIONS: [u32; 4] = [0, 5, 15, 60];
+/// Shortest wait before a looser limit takes effect, used when there is no cool-down.
+const LOOSEN_AFTER_MINUTES: u32 = 5;
+
+/// Player-chosen limits, kept in the save file. Values of 0 are off.
+#[derive(Resource, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SafeguardSettings {
+    /// Minutes of gamba between reality checks.
+    reality_check_minutes: u32,
+    /// Net loss per session at which betting stops.
+    loss_limit: u64,
+    /// Total amount wagered per session at which betting stops.
+    wager_cap: u64,
+    /// Minutes the gamba stays locked after taking a break or hitting a limit.
+    cooldown_minutes: u32,
+    /// Unix time in seconds until which the gamba is locked.
+    locked_until: Option<u64>,
+    /// Looser loss limit waiting for [`SafeguardSettings::loosen_at`].
+    pending_loss_limit: Option<u64>,
+    /// Looser wager cap waiting for [`SafeguardSettings::loosen_at`].
+    pending_wager_cap: Option<u64>,
+    /// Unix time in seconds from which the pending limits apply.
+    loosen_at: u64,
+}
+
+impl Default for SafeguardSettings {
+    fn default() -> Self {
+        Self {
+            reality_check_minutes: 30,
+            loss_limit: 0,
+            wager_cap: 0,
+            cooldown_minutes: 0,
+            locked_until: None,
+            pending_loss_limit: None,
+            pending_wager_cap: None,
+            loosen_at: 0,
+        }
+    }
+}
+
+impl SafeguardSettings {
+    /// Starts the cool-down, if there is one.
+    fn lock(&mut self) {
+        if self.cooldown_minutes > 0 {
+            self.locked_until = Some(unix_time() + self.cooldown_minutes as u64 * 60);
+        }
+    }
+
+    /// Seconds left of the cool-down.
+    fn lockout_remaining(&self) -> u64 {
+        self.locked_until
+            .map_or(0, |until| until.saturating_sub(unix_time()))
+    }
+
+    /// Tightens the loss limit right away, or loosens it once a cool-down has passed.
+    fn set_loss_limit(&mut self, limit: u64) {
+        if loosens(self.loss_limit, limit) {
+            self.pending_loss_limit = Some(limit);
+            self.loosen_at = self.loosen_time();
+        } else {
+            self.loss_limit = limit;
+            self.pending_loss_limit = None;
+        }
+    }
+
+    /// Tightens the wager cap right away, or loosens it once a cool-down has passed.
+    fn set_wager_cap(&mut self, cap: u64) {
+        if loosens(self.wager_cap, cap) {
+            self.pending_wager_cap = Some(cap);
+            self.loosen_at = self.loosen_time();
+        } else {
+            self.wager_cap = cap;
+            self.pending_wager_cap = None;
+        }
+    }
+
+    /// When a limit loosened now takes effect, so it can't be lifted right after it is reached.
+    /// That is after the cool-down, or [`LOOSEN_AFTER_MINUTES`] without one, and never before a
+    /// running cool-down is over.
+    fn loosen_time(&self) -> u64 {
+        let minutes = match self.cooldown_minutes {
+            0 => LOOSEN_AFTER_MINUTES,
+            minutes => minutes,
+        };
+        (unix_time() + minutes as u64 * 60).max(self.locked_until.unwrap_or(0))
+    }
+
+    /// Seconds left until the pending limits apply.
+    fn loosen_remaining(&self) -> u64 {
+        self.loosen_at.saturating_sub(unix_time())
+    }
+
+    fn loosening_due(&self) -> bool {
+        (self.pending_loss_limit.is_some() || self.pending_wager_cap.is_some())
+            && self.loosen_remaining() == 0
+    }
+
+    fn loosen(&mut self) {
+        if let Some(limit) = self.pending_loss_limit.take() {
+            self.loss_limit = limit;
+        }
+        if let Some(cap) = self.pending_wager_cap.take() {
+            self.wager_cap = cap;
+        }
+    }
+}
+
+/// Whether going from limit `from` to `to` allows more, where 0 is no limit at all.
+fn loosens(from: u64, to: u64) -> bool {
+    from > 0 && (to == 0 || to > from)
+}
+
+pub fn gamba_locked(settings: &SafeguardSettings) -> bool {
+    settings.lockout_remaining() > 0
+}
+
+/// What happened in the gamba and the casino since the game was started.
+#[derive(Resource, Default)]
+pub struct Session {
+    /// Seconds spent in the gamba, not counting reality checks.
+    played: f32,
+    since_reality_check: f32,
+    wagered: u64,
+    net: i64,
+}
+
+impl Session {
+    /// The session limit that has been reached, if any. Bets are refused with it as the reason.
+    pub fn limit_reached(&self, settings: &SafeguardSettings) -> Option<String> {
+        if settings.loss_limit > 0 && -self.net >= settings.loss_limit as i64 {
+            Some(format!("loss limit of ${} reached", settings.loss_limit))
+        } else if settings.wager_cap > 0 && self.wagered >= settings.wager_cap {
+            Some(format!("wager cap of ${} reached", settings.wager_cap))
+        } else {
+            None
+        }
+    }
+}
+
+fn minutes_name(minutes: u32) -> String {
+    match minutes {
+        0 => "off".into(),
+        _ => format!("{minutes} min"),
+    }
+}
+
+fn money_name(amount: u64) -> String {
+    match amount {
+        0 => "off".into(),
+        _ => format!("${amount}"),
+    }
+}
+
+/// A limit, followed by the looser one it is about to become.
+fn limit_name(limit: u64, pending: Option<u64>, remaining: u64) -> String {
+    match pending {
+        Some(pending) => format!(
+            "{}, {} in {}:{:02}",
+            money_name(limit),
+            money_name(pending),
+            remaining / 60,
+            remaining % 60,
+        ),
+        None => money_name(limit),
+    }
+}
+
+/// Changes one of the [`SafeguardSettings`] to the given value.
+#[derive(Message, Clone, Serialize, Deserialize)]
+pub enum SafeguardMessage {
+    RealityCheck(u32),
+    LossLimit(u64),
+    WagerCap(u64),
+    Cooldown(u32),
+}
+
+#[derive(Component)]
+enum SafeguardButton {
+    TogglePanel,
+    RealityCheck,
+    LossLimit,
+    WagerCap,
+    Cooldown,
+    /// Closes a dialog and keeps playing.
+    Continue,
+    /// Closes a dialog, starts the cool-down and leaves the gamba.
+    TakeBreak,
+}
+
+#[derive(Component)]
+struct SafeguardPanel;
+
+#[derive(Component)]
+enum SafeguardDisplay {
+    RealityCheck,
+    LossLimit,
+    WagerCap,
+    Cooldown,
+    Session,
+}
+
+#[derive(Component)]
+struct SafeguardDialog;
+
+#[derive(Component)]
+struct LockoutDisplay;
+
+fn setup_panel(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.),
+            position_type: PositionType::Absolute,
+            top: Val::Px(0.),
+            left: Val::Px(330.),
+            ..default()
+        },
+        OnBettingScreen,
+        children![
+            safeguard_button(SafeguardButton::TogglePanel, "limits"),
+            (
+                Node {
+                    display: Display::None,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.),
+                    padding: UiRect::all(Val::Px(8.)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba_u8(29, 29, 29, 160)),
+                SafeguardPanel,
+                children![
+                    setting_button(
+                        SafeguardButton::RealityCheck,
+                        "reality check every: ",
+                        SafeguardDisplay::RealityCheck
+                    ),
+                    setting_button(
+                        SafeguardButton::LossLimit,
+                        "loss limit: ",
+                        SafeguardDisplay::LossLimit
+                    ),
+                    setting_button(
+                        SafeguardButton::WagerCap,
+                        "wager cap: ",
+                        SafeguardDisplay::WagerCap
+                    ),
+                    setting_button(
+                        SafeguardButton::Cooldown,
+                        "cool-down: ",
+                        SafeguardDisplay::Cooldown
+                    ),
+                    (
+                        Text::default(),
+                        TextFont {
+                            font_size: 12.,
+                            ..default()
+                        },
+                        children![(TextSpan::default(), SafeguardDisplay::Session)],
+                    ),
+                ],
+            ),
+        ],
+    ));
+}
+
+fn safeguard_button(action: SafeguardButton, label: &str) -> impl Bundle + use<> {
+    (
+        Button,
+        action,
+        Node {
+            padding: UiRect::all(Val::Px(4.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgb_u8(69, 69, 69)),
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size: 14.,
+                ..default()
+            },
+        )],
+    )
+}
+
+/// A button that cycles a setting, followed by its current value.
+fn setting_button(
+    action: SafeguardButton,
+    label: &str,
+    display: SafeguardDisplay,
+) -> impl Bundle + use<> {
+    (
+        Button,
+        action,
+        Node {
+            padding: UiRect::all(Val::Px(4.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgb_u8(69, 69, 69)),
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size: 14.,
+                ..default()
+            },
+            children![(TextSpan::default(), display)],
+        )],
+    )
+}
+
+/// Covers the whole screen so nothing behind the dialog can be clicked.
+fn spawn_dialog(commands: &mut Commands, message: String) {
+    commands.spawn((
+        Node {
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        BackgroundColor(Color::srgba_u8(0, 0, 0, 160)),
+        GlobalZIndex(20),
+        SafeguardDialog,
+        OnBettingScreen,
+        children![(
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(12.),
+                padding: UiRect::all(Val::Px(24.)),
+                ..default()
+            },
+            BackgroundColor(Color::srgb_u8(200, 200, 200)),
+            children![
+                (
+                    Text::new(message),
+                    TextFont {
+                        font_size: 24.,
+                        ..default()
+                    },
+                    TextColor(Color::srgb_u8(29, 29, 29)),
+                ),
+                (
+                    Node {
+                        column_gap: Val::Px(12.),
+                        ..default()
+                    },
+                    children![
+                        safeguard_button(SafeguardButton::Continue, "keep playing"),
+                        safeguard_button(SafeguardButton::TakeBreak, "take a break"),
+                    ],
+                ),
+            ],
+        )],
+    ));
+}
+
+fn track_session(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut result_messages: MessageReader<BetResultMessage>,
+    mut session: ResMut<Session>,
+    mut settings: ResMut<SafeguardSettings>,
+) {
+    if settings.loosening_due() {
+        settings.loosen();
+    }
+    session.played += time.delta_secs();
+    session.since_reality_check += time.delta_secs();
+    for result in result_messages.read() {
+        let was_blocked = session.limit_reached(&settings).is_some();
+        session.wagered += result.stake;
+        session.net += result.net();
+        if let Some(reason) = session.limit_reached(&settings)
+            && !was_blocked
+        {
+            settings.lock();
+            spawn_dialog(
+                &mut commands,
+                format!("The {reason}.\nNo more bets this session."),
+            );
+        }
+    }
+}
+
+/// Pauses the game every few minutes to show how long the player has been at it.
+fn reality_check(
+    mut commands: Commands,
+    mut session: ResMut<Session>,
+    settings: Res<SafeguardSettings>,
+    mut time: ResMut<Time<Virtual>>,
+    casino_round: Option<Res<CasinoRound>>,
+) {
+    let interval = settings.reality_check_minutes as f32 * 60.;
+    // Waits for a casino round to finish, taking a break can't leave in the middle of one.
+    if interval == 0. || session.since_reality_check < interval || casino_round.is_some() {
+        return;
+    }
+    session.since_reality_check = 0.;
+    time.pause();
+    spawn_dialog(
+        &mut commands,
+        format!(
+            "Reality check!\nYou've played for {} minutes.\nNet result this session: {:+} $",
+            (session.played / 60.).round(),
+            session.net,
+        ),
+    );
+}
+
+fn handle_safeguard_buttons(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &SafeguardButton), Changed<Interaction>>,
+    mut settings: ResMut<SafeguardSettings>,
+    mut panels: Query<&mut Node, With<SafeguardPanel>>,
+    dialogs: Query<Entity, With<SafeguardDialog>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut safeguard_messages: MessageWriter<SafeguardMessage>,
+) {
+    for (interaction, button) in interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            SafeguardButton::TogglePanel => {
+                for mut panel in &mut panels {
+                    panel.display = match panel.display {
+                        Display::None => Display::Flex,
+                        _ => Display::None,
+                    };
+                }
+            }
+            SafeguardButton::RealityCheck => {
+                safeguard_messages.write(SafeguardMessage::RealityCheck(cycle(
+                    &REALITY_CHECK_OPTIONS,
+                    settings.reality_check_minutes,
+                )));
+            }
+            // Cycles on from a pending limit, which is the one shown last.
+            SafeguardButton::LossLimit => {
+                safeguard_messages.write(SafeguardMessage::LossLimit(cycle(
+                    &LOSS_LIMIT_OPTIONS,
+                    settings.pending_loss_limit.unwrap_or(settings.loss_limit),
+                )));
+            }
+            SafeguardButton::WagerCap => {
+                safeguard_messages.write(SafeguardMessage::WagerCap(cycle(
+                    &WAGER_CAP_OPTIONS,
+                    settings.pending_wager_cap.unwrap_or(settings.wager_cap),
+                )));
+            }
+            SafeguardButton::Cooldown => {
+                safeguard_messages.write(SafeguardMessage::Cooldown(cycle(
+                    &COOLDOWN_OPTIONS,
+                    settings.cooldown_minutes,
+                )));
+            }
+            SafeguardButton::Continue | SafeguardButton::TakeBreak => {
+                for dialog in &dialogs {
+                    commands.entity(dialog).despawn();
+                }
+                time.unpause();
+                if matches!(button, SafeguardButton::TakeBreak) {
+                    settings.lock();
+                    game_state.set(GameState::Cake);
+                }
+            }
+        }
+    }
+}
+
+fn handle_safeguard_messages(
+    mut safeguard_messages: MessageReader<SafeguardMessage>,
+    mut settings: ResMut<SafeguardSettings>,
+) {
+    for msg in safeguard_messages.read() {
+        match *msg {
+            SafeguardMessage::RealityCheck(minutes) => settings.reality_check_minutes = minutes,
+            SafeguardMessage::LossLimit(limit) => settings.set_loss_limit(limit),
+            SafeguardMessage::WagerCap(cap) => settings.set_wager_cap(cap),
+            SafeguardMessage::Cooldown(minutes) => settings.cooldown_minutes = minutes,
+        }
+    }
+}
+
+fn update_safeguard_panel(
+    settings: Res<SafeguardSettings>,
+    session: Res<Session>,
+    mut spans: Query<(&mut TextSpan, &SafeguardDisplay)>,
+) {
+    for (mut span, display) in &mut spans {
+        let text = match display {
+            SafeguardDisplay::RealityCheck => minutes_name(settings.reality_check_minutes),
+            SafeguardDisplay::LossLimit => limit_name(
+                settings.loss_limit,
+                settings.pending_loss_limit,
+                settings.loosen_remaining(),
+            ),
+            SafeguardDisplay::WagerCap => limit_name(
+                settings.wager_cap,
+                settings.pending_wager_cap,
+                settings.loosen_remaining(),
+            ),
+            SafeguardDisplay::Cooldown => minutes_name(settings.cooldown_minutes),
+            SafeguardDisplay::Session => format!(
+                "this session: {:.0} min, wagered ${}, {:+} $",
+                session.played / 60.,
+                session.wagered,
+                session.net,
+            ),
+        };
+        // Only touch the text when it changes, the session line is refreshed every frame.
+        if **span != text {
+            **span = text;
+        }
+    }
+}
+
+/// Makes sure a reality check never leaves the game paused after leaving the gamba.
+fn resume(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn setup_cake(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(64.),
+            right: Val::Px(0.),
+            padding: UiRect::all(Val::Px(4.)),
+            ..default()
+        },
+        OnCakeScreen,
+        children![(
+            Text::default(),
+            TextFont {
+                font_size: 12.,
+                ..default()
+            },
+            TextColor(Color::srgb_u8(69, 69, 69)),
+            LockoutDisplay,
+        )],
+    ));
+}
+
+fn update_lockout_display(
+    settings: Res<SafeguardSettings>,
+    mut display: Query<&mut Text, With<LockoutDisplay>>,
+) {
+    let remaining = settings.lockout_remaining();
+    let text = match remaining {
+        0 => String::new(),
+        _ => format!("gamba locked for {}:{:02}", remaining / 60, remaining % 60),
+    };
+    for mut display in &mut display {
+        if **display != text {
+            **display = text.clone();
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    GameState,
+    credit::Credit,
+    economy::{Pantry, Score},
+    gamba::{Bet, SugarCaneHeight, fishing::FishingLog, income::Income, market::Market},
+    replay::live_input,
+    safeguards::SafeguardSettings,
+    storage,
+};
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Stats>()
+            .insert_resource(SaveDebounce {
+                timer: Timer::from_seconds(SAVE_DEBOUNCE_SECS, TimerMode::Once),
+                max_wait: Timer::from_seconds(SAVE_MAX_WAIT_SECS, TimerMode::Once),
+                pending: false,
+            })
+            .add_systems(OnEnter(GameState::AssetLoading), load_save)
+            .add_systems(
+                Update,
+                queue_save.run_if(not(in_state(GameState::AssetLoading)).and(live_input)),
+            )
+            .add_systems(Last, flush_save_on_exit.run_if(live_input));
+    }
+}
+
+const SAVE_FILE: &str = "save.json";
+/// Saves start at version 1 and every migration adds one.
+const SAVE_VERSION: u64 = MIGRATIONS.len() as u64 + 1;
+const SAVE_DEBOUNCE_SECS: f32 = 1.;
+/// Longest a change waits to be saved while the game keeps changing.
+const SAVE_MAX_WAIT_SECS: f32 = 10.;
+
+/// Upgrades a save from version `i + 1` to version `i + 2`.
+///
+/// Append a step here whenever [`SaveData`] changes in a way `#[serde(default)]` can't paper
+/// over, which also bumps [`SAVE_VERSION`].
+const MIGRATIONS: &[fn(&mut Value)] = &[];
+
+/// Lifetime statistics kept across sessions.
+#[derive(Resource, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Stats {
+    pub cakes_eaten: u64,
+    pub flames_extinguished: u64,
+    pub bankruptcies: u64,
+    pub pickle_sightings: u64,
+    pub wishes_made: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveData {
+    version: u64,
+    score: u64,
+    bet: u64,
+    stats: Stats,
+    #[serde(default)]
+    credit: Credit,
+    #[serde(default)]
+    safeguards: SafeguardSettings,
+    #[serde(default)]
+    fishing: FishingLog,
+    #[serde(default)]
+    canes: SugarCaneHeight,
+    #[serde(default)]
+    market: Market,
+    #[serde(default)]
+    income: Income,
+    #[serde(default)]
+    pantry: Pantry,
+}
+
+#[derive(Resource)]
+struct SaveDebounce {
+    /// Restarted by every change.
+    timer: Timer,
+    /// Started by the first change since the last save.
+    max_wait: Timer,
+    pending: bool,
+}
+
+fn parse_save(contents: &str) -> Result<SaveData, String> {
+    let mut save: Value = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+    migrate(&mut save, MIGRATIONS)?;
+    serde_json::from_value(save).map_err(|e| e.to_string())
+}
+
+/// Runs the `migrations` from the save's version up to the last one. Saves from a newer
+/// version of the game are refused rather than guessed at.
+fn migrate(save: &mut Value, migrations: &[fn(&mut Value)]) -> Result<(), String> {
+    let latest = migrations.len() as u64 + 1;
+    let version = save
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or("save has no version")?;
+    if version == 0 || version > latest {
+        return Err(format!("unsupported save version {version}"));
+    }
+    for migration in &migrations[version as usize - 1..] {
+        migration(save);
+    }
+    save["version"] = latest.into();
+    Ok(())
+}
+
+fn load_save(mut commands: Commands) {
+    let Some(contents) = storage::read(SAVE_FILE) else {
+        return;
+    };
+    match parse_save(&contents) {
+        Ok(save) => {
+            let score = save.score.max(1);
+            commands.insert_resource(Score(score));
+            commands.insert_resource(Bet(save.bet.clamp(1, score)));
+            commands.insert_resource(save.stats);
+            commands.insert_resource(save.credit);
+            commands.insert_resource(save.safeguards);
+            commands.insert_resource(save.fishing);
+            commands.insert_resource(save.canes);
+            commands.insert_resource(save.market);
+            commands.insert_resource(save.income);
+            commands.insert_resource(save.pantry);
+        }
+        Err(e) => warn!("Ignoring save file: {e}"),
+    }
+}
+
+fn write_save(
+    score: &Score,
+    bet: &Bet,
+    stats: &Stats,
+    credit: &Credit,
+    safeguards: &SafeguardSettings,
+    fishing: &FishingLog,
+    canes: &SugarCaneHeight,
+    market: &Market,
+    income: &Income,
+    pantry: &Pantry,
+) {
+    let save = SaveData {
+        version: SAVE_VERSION,
+        score: score.0,
+        bet: bet.0,
+        stats: stats.clone(),
+        credit: credit.clone(),
+        safeguards: safeguards.clone(),
+        fishing: fishing.clone(),
+        canes: canes.clone(),
+        market: market.clone(),
+        income: income.clone(),
+        pantry: pantry.clone(),
+    };
+    let result = serde_json::to_string(&save)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| storage::write(SAVE_FILE, &contents));
+    if let Err(e) = result {
+        warn!("Failed to save: {e}");
+    }
+}
+
+fn queue_save(
+    time: Res<Time>,
+    mut debounce: ResMut<SaveDebounce>,
+    score: Res<Score>,
+    bet: Res<Bet>,
+    stats: Res<Stats>,
+    credit: Res<Credit>,
+    safeguards: Res<SafeguardSettings>,
+    fishing: Res<FishingLog>,
+    canes: Res<SugarCaneHeight>,
+    market: Res<Market>,
+    income: Res<Income>,
+    pantry: Res<Pantry>,
+) {
+    if score.is_changed()
+        || bet.is_changed()
+        || stats.is_changed()
+        || credit.is_changed()
+        || safeguards.is_changed()
+        || fishing.is_changed()
+        || canes.is_changed()
+        || market.is_changed()
+        || income.is_changed()
+        || pantry.is_changed()
+    {
+        if !debounce.pending {
+            debounce.max_wait.reset();
+        }
+        debounce.pending = true;
+        debounce.timer.reset();
+    }
+    debounce.timer.tick(time.delta());
+    debounce.max_wait.tick(time.delta());
+    if debounce.pending && (debounce.timer.is_finished() || debounce.max_wait.is_finished()) {
+        debounce.pending = false;
+        write_save(
+            &score,
+            &bet,
+            &stats,
+            &credit,
+            &safeguards,
+            &fishing,
+            &canes,
+            &market,
+            &income,
+            &pantry,
+        );
+    }
+}
+
+fn flush_save_on_exit(
+    mut exit_messages: MessageReader<AppExit>,
+    debounce: Res<SaveDebounce>,
+    score: Res<Score>,
+    bet: Res<Bet>,
+    stats: Res<Stats>,
+    credit: Res<Credit>,
+    safeguards: Res<SafeguardSettings>,
+    fishing: Res<FishingLog>,
+    canes: Res<SugarCaneHeight>,
+    market: Res<Market>,
+    income: Res<Income>,
+    pantry: Res<Pantry>,
+) {
+    if exit_messages.read().next().is_some() && debounce.pending {
+        write_save(
+            &score,
+            &bet,
+            &stats,
+            &credit,
+            &safeguards,
+            &fishing,
+            &canes,
+            &market,
+            &income,
+            &pantry,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A save as the first versioned release wrote it, before any of the later fields.
+    const V1_SAVE: &str = r#"{
+        "version": 1,
+        "score": 42,
+        "bet": 5,
+        "stats": {
+            "cakes_eaten": 3,
+            "flames_extinguished": 27,
+            "bankruptcies": 1,
+            "pickle_sightings": 2
+        }
+    }"#;
+
+    #[test]
+    fn loads_a_v1_save() {
+        let save = parse_save(V1_SAVE).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.score, 42);
+        assert_eq!(save.bet, 5);
+        assert_eq!(save.stats.cakes_eaten, 3);
+        assert_eq!(save.stats.flames_extinguished, 27);
+        assert_eq!(save.stats.wishes_made, 0);
+        assert_eq!(save.credit.debt, 0);
+    }
+
+    #[test]
+    fn refuses_saves_from_a_newer_version() {
+        let newer = V1_SAVE.replace(
+            r#""version": 1"#,
+            &format!(r#""version": {}"#, SAVE_VERSION + 1),
+        );
+        assert!(parse_save(&newer).is_err());
+        assert!(parse_save(&V1_SAVE.replace(r#""version": 1"#, r#""version": 0"#)).is_err());
+    }
+
+    #[test]
+    fn migrations_run_in_order_from_the_stored_version() {
+        let migrations: &[fn(&mut Value)] = &[
+            |save| save["steps"] = "1".into(),
+            |save| {
+                let steps = save["steps"].as_str().unwrap_or_default().to_owned();
+                save["steps"] = format!("{steps}2").into();
+            },
+        ];
+
+        let mut save = serde_json::json!({ "version": 1 });
+        migrate(&mut save, migrations).unwrap();
+        assert_eq!(save, serde_json::json!({ "version": 3, "steps": "12" }));
+
+        let mut save = serde_json::json!({ "version": 2, "steps": "x" });
+        migrate(&mut save, migrations).unwrap();
+        assert_eq!(save, serde_json::json!({ "version": 3, "steps": "x2" }));
+    }
+}
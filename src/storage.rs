@@ -0,0 +1,49 @@
+//! Small named blobs of text that outlive the game: files under the user data directory on
+//! native builds and `localStorage` entries on the web.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::{fs, path::PathBuf};
+
+    use directories::ProjectDirs;
+
+    fn path(name: &str) -> Option<PathBuf> {
+        ProjectDirs::from("", "KapitelkaMLG", "cake-for-mew").map(|dirs| dirs.data_dir().join(name))
+    }
+
+    pub fn read(name: &str) -> Option<String> {
+        fs::read_to_string(path(name)?).ok()
+    }
+
+    pub fn write(name: &str, contents: &str) -> Result<(), String> {
+        let path = path(name).ok_or("no user data directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, contents).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    fn key(name: &str) -> String {
+        format!("cake-for-mew/{name}")
+    }
+
+    pub fn read(name: &str) -> Option<String> {
+        local_storage()?.get_item(&key(name)).ok()?
+    }
+
+    pub fn write(name: &str, contents: &str) -> Result<(), String> {
+        local_storage()
+            .ok_or("localStorage is unavailable")?
+            .set_item(&key(name), contents)
+            .map_err(|e| format!("{e:?}"))
+    }
+}
+
+pub use platform::{read, write};
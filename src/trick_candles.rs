@@ -0,0 +1,339 @@
+use bevy::prelude::*;
+use bevy_light_2d::prelude::*;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState,
+    animation::{AnimationClips, AnimationFinished, DespawnOnFinish},
+    assets::Assets,
+    cake::{CakeDefinition, FLAME_LIGHT_RADIUS, Flame},
+    replay::live_input,
+    tween::{Tween, TweenTarget},
+    util::despawn_all,
+};
+
+/// Blown out candles that light themselves again, set up per cake with
+/// [`CakeDefinition::trick_candles`], and the mode where every candle has to be kept out for a
+/// while.
+pub struct TrickCandlePlugin;
+
+impl Plugin for TrickCandlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KeepOut>()
+            .add_message::<TrickCandleMessage>()
+            .add_systems(OnEnter(GameState::Cake), setup)
+            .add_systems(
+                Update,
+                (
+                    tick_keep_out,
+                    (tick_relights, check_keep_out).run_if(live_input),
+                    handle_trick_candle_messages,
+                    (finish_sputter, hide_burned_out_smoke, update_keep_out_meter),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Cake)),
+            )
+            .add_systems(OnExit(GameState::Cake), despawn_all::<OnCakeScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnCakeScreen;
+
+/// How much faster than usual the eating sound plays as the sputter of a relighting candle.
+const SPUTTER_SOUND_SPEED: f32 = 2.5;
+const KEEP_OUT_METER_WIDTH: f32 = 300.;
+
+#[derive(Deserialize, Clone)]
+pub struct TrickCandles {
+    /// Chance from 0 to 1 that a blown out candle lights itself again.
+    chance: f32,
+    /// Seconds until it does, `(min, max)`.
+    delay: (f32, f32),
+    /// Seconds every candle has to stay out for, `None` to not play the keep them out mode.
+    #[serde(default)]
+    keep_out: Option<f32>,
+}
+
+impl TrickCandles {
+    /// Decides whether a candle that was just blown out relights, and after how many seconds.
+    pub fn roll(&self, rng: &mut WyRand) -> Option<f32> {
+        let unit = |rng: &mut WyRand| rng.next_u32() as f32 / u32::MAX as f32;
+        if unit(rng) >= self.chance {
+            return None;
+        }
+        Some(self.delay.0 + (self.delay.1 - self.delay.0) * unit(rng))
+    }
+}
+
+/// A smoking candle waiting to light itself again.
+#[derive(Component)]
+pub struct Relight {
+    slot: usize,
+    timer: Timer,
+}
+
+impl Relight {
+    pub fn new(slot: usize, delay: f32) -> Self {
+        Self {
+            slot,
+            timer: Timer::from_seconds(delay, TimerMode::Once),
+        }
+    }
+}
+
+/// A relit flame still sputtering back to life.
+#[derive(Component)]
+struct Sputtering;
+
+/// A flame that lit itself again rather than being lit with a fresh set of candles.
+#[derive(Component)]
+pub struct Relit;
+
+#[derive(Message, Clone, Serialize, Deserialize)]
+pub enum TrickCandleMessage {
+    /// The smoking candle in the slot lights itself again.
+    Relight { slot: usize },
+    /// Every candle stayed out for long enough, none of them relight any more.
+    KeptOut,
+}
+
+/// Progress of the keep them out mode.
+#[derive(Resource, Default)]
+struct KeepOut {
+    /// Whether there have been candles to keep out since the last one burned.
+    lit: bool,
+    /// Seconds since the last flame went out.
+    elapsed: f32,
+    done: bool,
+}
+
+#[derive(Component)]
+struct KeepOutMeter;
+
+#[derive(Component)]
+struct KeepOutLabel;
+
+#[derive(Component)]
+struct KeepOutFill;
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(4.),
+            width: Val::Percent(100.),
+            position_type: PositionType::Absolute,
+            top: Val::Percent(20.),
+            display: Display::None,
+            ..default()
+        },
+        KeepOutMeter,
+        OnCakeScreen,
+        children![
+            (
+                Text::default(),
+                TextFont {
+                    font_size: 20.,
+                    ..default()
+                },
+                KeepOutLabel,
+            ),
+            (
+                Node {
+                    width: Val::Px(KEEP_OUT_METER_WIDTH),
+                    height: Val::Px(12.),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba_u8(69, 69, 69, 160)),
+                children![(
+                    Node {
+                        width: Val::Percent(0.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb_u8(243, 207, 198)),
+                    KeepOutFill,
+                )],
+            ),
+        ],
+    ));
+}
+
+/// Counts how long every flame has been out, starting over whenever one burns.
+fn tick_keep_out(time: Res<Time>, mut keep_out: ResMut<KeepOut>, flames: Query<(), With<Flame>>) {
+    if !flames.is_empty() {
+        *keep_out = KeepOut {
+            lit: true,
+            ..default()
+        };
+    } else if keep_out.lit && !keep_out.done {
+        keep_out.elapsed += time.delta_secs();
+    }
+}
+
+fn tick_relights(
+    time: Res<Time>,
+    mut relights: Query<&mut Relight>,
+    mut trick_candle_messages: MessageWriter<TrickCandleMessage>,
+) {
+    for mut relight in &mut relights {
+        relight.timer.tick(time.delta());
+        if relight.timer.just_finished() {
+            trick_candle_messages.write(TrickCandleMessage::Relight { slot: relight.slot });
+        }
+    }
+}
+
+fn check_keep_out(
+    keep_out: Res<KeepOut>,
+    assets: Res<Assets>,
+    definitions: Res<bevy::asset::Assets<CakeDefinition>>,
+    mut trick_candle_messages: MessageWriter<TrickCandleMessage>,
+) {
+    let Some(seconds) = keep_out_seconds(&assets, &definitions) else {
+        return;
+    };
+    if !keep_out.done && keep_out.elapsed >= seconds {
+        trick_candle_messages.write(TrickCandleMessage::KeptOut);
+    }
+}
+
+fn handle_trick_candle_messages(
+    mut commands: Commands,
+    mut trick_candle_messages: MessageReader<TrickCandleMessage>,
+    mut relights: Query<(Entity, &Relight, &mut Sprite, &mut PointLight2d)>,
+    mut keep_out: ResMut<KeepOut>,
+    clips: AnimationClips,
+    assets: Res<Assets>,
+) {
+    for msg in trick_candle_messages.read() {
+        match msg {
+            TrickCandleMessage::Relight { slot } => {
+                for (entity, relight, mut sprite, mut light) in &mut relights {
+                    if relight.slot != *slot {
+                        continue;
+                    }
+                    let sputter = clips.get("flame_sputter");
+                    if let Some(atlas) = &mut sprite.texture_atlas {
+                        atlas.index = sputter.index();
+                    }
+                    light.radius = FLAME_LIGHT_RADIUS;
+                    commands.entity(entity).remove::<Relight>().insert((
+                        sputter,
+                        Sputtering,
+                        Relit,
+                        Flame { slot: *slot },
+                        Visibility::Inherited,
+                        Pickable::default(),
+                        Tween::new(
+                            TweenTarget::LightIntensity { start: 0., end: 3. },
+                            0.3,
+                            EaseFunction::QuadraticOut,
+                        )
+                        .then(
+                            TweenTarget::LightIntensity { start: 3., end: 2. },
+                            0.5,
+                            EaseFunction::QuadraticInOut,
+                        ),
+                    ));
+                    commands.spawn((
+                        AudioPlayer(assets.eating_sound.clone()),
+                        PlaybackSettings::DESPAWN.with_speed(SPUTTER_SOUND_SPEED),
+                    ));
+                }
+            }
+            TrickCandleMessage::KeptOut => {
+                keep_out.done = true;
+                for (entity, _, _, _) in &relights {
+                    commands
+                        .entity(entity)
+                        .remove::<Relight>()
+                        .insert((clips.get("smoke_puff"), DespawnOnFinish));
+                }
+            }
+        }
+    }
+}
+
+/// Lets a relit flame burn normally once it has sputtered back to life.
+fn finish_sputter(
+    mut commands: Commands,
+    mut finished_messages: MessageReader<AnimationFinished>,
+    sputtering: Query<(), (With<Sputtering>, With<Flame>)>,
+    clips: AnimationClips,
+) {
+    for msg in finished_messages.read() {
+        if sputtering.contains(msg.entity) {
+            commands
+                .entity(msg.entity)
+                .remove::<Sputtering>()
+                .insert(clips.get("flame_flicker"));
+        }
+    }
+}
+
+/// Hides a smoking candle once its smoke has cleared, until it lights itself again.
+fn hide_burned_out_smoke(
+    mut finished_messages: MessageReader<AnimationFinished>,
+    mut relights: Query<&mut Visibility, With<Relight>>,
+) {
+    for msg in finished_messages.read() {
+        if let Ok(mut visibility) = relights.get_mut(msg.entity) {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+fn update_keep_out_meter(
+    keep_out: Res<KeepOut>,
+    assets: Res<Assets>,
+    definitions: Res<bevy::asset::Assets<CakeDefinition>>,
+    mut meter: Single<&mut Node, (With<KeepOutMeter>, Without<KeepOutFill>)>,
+    mut fill: Single<&mut Node, (With<KeepOutFill>, Without<KeepOutMeter>)>,
+    mut label: Single<&mut Text, With<KeepOutLabel>>,
+) {
+    let Some(seconds) = keep_out_seconds(&assets, &definitions) else {
+        if meter.display != Display::None {
+            meter.display = Display::None;
+        }
+        return;
+    };
+    if meter.display != Display::Flex {
+        meter.display = Display::Flex;
+    }
+
+    let progress = if keep_out.done {
+        1.
+    } else {
+        (keep_out.elapsed / seconds.max(f32::EPSILON)).min(1.)
+    };
+    let width = Val::Percent(progress * 100.);
+    if fill.width != width {
+        fill.width = width;
+    }
+    let text = if keep_out.done {
+        "they stayed out!".to_string()
+    } else {
+        format!(
+            "keep every candle out for {seconds:.0}s: {:.1}s",
+            keep_out.elapsed.min(seconds)
+        )
+    };
+    if ***label != text {
+        ***label = text;
+    }
+}
+
+fn keep_out_seconds(
+    assets: &Assets,
+    definitions: &bevy::asset::Assets<CakeDefinition>,
+) -> Option<f32> {
+    definitions
+        .get(&assets.cake)
+        .and_then(|definition| definition.trick_candles.as_ref())
+        .and_then(|trick_candles| trick_candles.keep_out)
+}
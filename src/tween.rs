@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_light_2d::prelude::*;
+
+pub struct TweenPlugin;
+
+impl Plugin for TweenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<TweenCompleted>()
+            .add_systems(Update, run_tweens);
+    }
+}
+
+/// Property animated by a [`Tween`], with the values it moves between. Rotations are around the
+/// z axis, in radians.
+#[derive(Clone, Copy)]
+pub enum TweenTarget {
+    Translation { start: Vec3, end: Vec3 },
+    Scale { start: Vec3, end: Vec3 },
+    Rotation { start: f32, end: f32 },
+    UiScale { start: Vec2, end: Vec2 },
+    SpriteColor { start: Color, end: Color },
+    BackgroundColor { start: Color, end: Color },
+    LightIntensity { start: f32, end: f32 },
+}
+
+#[derive(Clone, Copy)]
+struct TweenStep {
+    target: TweenTarget,
+    duration: f32,
+    ease: EaseFunction,
+    /// Play the step forwards and then backwards again, taking twice as long.
+    yoyo: bool,
+}
+
+/// Smoothly animates a property of the entity through one or more chained steps.
+///
+/// The component removes itself and sends a [`TweenCompleted`] once the last step is done.
+#[derive(Component)]
+pub struct Tween {
+    steps: VecDeque<TweenStep>,
+    elapsed: f32,
+}
+
+impl Tween {
+    pub fn new(target: TweenTarget, duration: f32, ease: EaseFunction) -> Self {
+        Self {
+            steps: VecDeque::from([TweenStep {
+                target,
+                duration,
+                ease,
+                yoyo: false,
+            }]),
+            elapsed: 0.,
+        }
+    }
+
+    /// Runs another step once the previous ones have finished.
+    pub fn then(mut self, target: TweenTarget, duration: f32, ease: EaseFunction) -> Self {
+        self.steps.push_back(TweenStep {
+            target,
+            duration,
+            ease,
+            yoyo: false,
+        });
+        self
+    }
+
+    /// Makes the last step return to where it started.
+    pub fn with_yoyo(mut self) -> Self {
+        if let Some(step) = self.steps.back_mut() {
+            step.yoyo = true;
+        }
+        self
+    }
+}
+
+/// Sent when every step of an entity's [`Tween`] has finished.
+#[derive(Message)]
+pub struct TweenCompleted {
+    pub entity: Entity,
+}
+
+fn run_tweens(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(
+        Entity,
+        &mut Tween,
+        Option<&mut Transform>,
+        Option<&mut UiTransform>,
+        Option<&mut Sprite>,
+        Option<&mut BackgroundColor>,
+        Option<&mut PointLight2d>,
+    )>,
+    mut completed_messages: MessageWriter<TweenCompleted>,
+) {
+    for (
+        entity,
+        mut tween,
+        mut transform,
+        mut ui_transform,
+        mut sprite,
+        mut background,
+        mut light,
+    ) in &mut query
+    {
+        tween.elapsed += time.delta_secs();
+        let Some(step) = tween.steps.front().copied() else {
+            continue;
+        };
+
+        let length = if step.yoyo {
+            step.duration * 2.
+        } else {
+            step.duration
+        };
+        let progress = if length > 0. {
+            (tween.elapsed / length).min(1.)
+        } else {
+            1.
+        };
+        let fraction = if step.yoyo {
+            1. - (1. - 2. * progress).abs()
+        } else {
+            progress
+        };
+        let t = step.ease.sample_clamped(fraction);
+
+        match step.target {
+            TweenTarget::Translation { start, end } => {
+                if let Some(transform) = &mut transform {
+                    transform.translation = start.lerp(end, t);
+                }
+            }
+            TweenTarget::Scale { start, end } => {
+                if let Some(transform) = &mut transform {
+                    transform.scale = start.lerp(end, t);
+                }
+            }
+            TweenTarget::Rotation { start, end } => {
+                if let Some(transform) = &mut transform {
+                    transform.rotation = Quat::from_rotation_z(start.lerp(end, t));
+                }
+            }
+            TweenTarget::UiScale { start, end } => {
+                if let Some(ui_transform) = &mut ui_transform {
+                    ui_transform.scale = start.lerp(end, t);
+                }
+            }
+            TweenTarget::SpriteColor { start, end } => {
+                if let Some(sprite) = &mut sprite {
+                    sprite.color = start.mix(&end, t);
+                }
+            }
+            TweenTarget::BackgroundColor { start, end } => {
+                if let Some(background) = &mut background {
+                    background.0 = start.mix(&end, t);
+                }
+            }
+            TweenTarget::LightIntensity { start, end } => {
+                if let Some(light) = &mut light {
+                    light.intensity = start.lerp(end, t);
+                }
+            }
+        }
+
+        if progress >= 1. {
+            tween.elapsed -= length;
+            tween.steps.pop_front();
+            if tween.steps.is_empty() {
+                commands.entity(entity).remove::<Tween>();
+                completed_messages.write(TweenCompleted { entity });
+            }
+        }
+    }
+}
@@ -2,8 +2,18 @@ use bevy::prelude::*;
 
 use crate::{
     GameState,
-    gamba::{Bet, BetChange, BetChangeMessage, BetMessage, Score, Side},
-    util::despawn_all,
+    calendar::{Celebration, celebrate_today},
+    economy::Score,
+    gamba::{
+        Bet, BetChange, BetChangeMessage, BetMessage, BetRejectedMessage, BetResultMessage,
+        GambaRoundState, SettledRound, betting_open,
+    },
+    recipient::Recipient,
+    replay::live_input,
+    rules::{BetKind, Settlement, Side, side_name},
+    safeguards::{SafeguardSettings, gamba_locked},
+    tween::{Tween, TweenTarget},
+    util::{cycle, despawn_all},
 };
 
 pub struct UiPlugin;
@@ -15,12 +25,25 @@ impl Plugin for UiPlugin {
                 OnExit(GameState::AssetLoading),
                 despawn_all::<OnAssetLoadingScreen>,
             )
-            .add_systems(OnEnter(GameState::Cake), setup_cake)
+            .add_systems(OnEnter(GameState::Cake), setup_cake.after(celebrate_today))
+            .add_systems(Update, update_banner.run_if(in_state(GameState::Cake)))
             .add_systems(OnExit(GameState::Cake), despawn_all::<OnCakeScreen>)
+            .init_resource::<AutoBet>()
             .add_systems(OnEnter(GameState::Gamba), setup_gamba)
-            .add_systems(Update, update_displays.run_if(in_state(GameState::Gamba)))
-            .add_systems(OnExit(GameState::Gamba), despawn_all::<OnGambaScreen>)
-            .add_systems(Update, handle_buttons);
+            .add_systems(
+                Update,
+                (
+                    run_auto_bet.run_if(live_input),
+                    update_displays,
+                    update_auto_bet_panel,
+                )
+                    .run_if(in_state(GameState::Gamba)),
+            )
+            .add_systems(
+                OnExit(GameState::Gamba),
+                (despawn_all::<OnGambaScreen>, stop_auto_bet),
+            )
+            .add_systems(Update, handle_buttons.run_if(live_input));
     }
 }
 
@@ -50,7 +73,10 @@ fn setup_asset_loading(mut commands: Commands) {
 #[derive(Component)]
 struct OnCakeScreen;
 
-fn setup_cake(mut commands: Commands) {
+#[derive(Component)]
+struct Banner;
+
+fn setup_cake(mut commands: Commands, recipient: Res<Recipient>, celebration: Res<Celebration>) {
     commands.spawn((
         Node {
             align_items: AlignItems::Center,
@@ -60,19 +86,29 @@ fn setup_cake(mut commands: Commands) {
             ..default()
         },
         OnCakeScreen,
+        UiTransform::from_scale(Vec2::ZERO),
+        Tween::new(
+            TweenTarget::UiScale {
+                start: Vec2::ZERO,
+                end: Vec2::ONE,
+            },
+            0.6,
+            EaseFunction::BounceOut,
+        ),
         children![(
-            Text::new("Happy birthday, Mew <3 <3 <3"),
+            Text::new(celebration.banner(&recipient)),
             TextFont {
                 font_size: 69.,
                 ..default()
             },
-            TextColor(Color::srgb_u8(243, 207, 198)),
+            TextColor(recipient.color()),
+            Banner,
         )],
     ));
 
     commands.spawn((
         Button,
-        ButtonAction::Gamba,
+        ButtonAction::Casino,
         Node {
             align_items: AlignItems::Center,
             justify_content: JustifyContent::Center,
@@ -95,6 +131,20 @@ fn setup_cake(mut commands: Commands) {
     ));
 }
 
+/// Follows the birthday person when the calendar passes the cake on.
+fn update_banner(
+    recipient: Res<Recipient>,
+    celebration: Res<Celebration>,
+    mut banner: Query<&mut Text, With<Banner>>,
+) {
+    if !recipient.is_changed() && !celebration.is_changed() {
+        return;
+    }
+    for mut text in &mut banner {
+        **text = celebration.banner(&recipient);
+    }
+}
+
 #[derive(Component)]
 struct OnGambaScreen;
 
@@ -104,6 +154,162 @@ struct ScoreDisplay;
 #[derive(Component)]
 struct BetDisplay;
 
+/// Seconds between auto-bet rounds once the camera has stopped moving.
+const AUTO_BET_DELAY: f32 = 0.5;
+/// How long auto-bet waits for a bet to be settled before giving up on the run.
+const AUTO_BET_TIMEOUT: f32 = 10.;
+const AUTO_BET_PROFIT_TARGETS: [u64; 5] = [0, 10, 50, 100, 500];
+const AUTO_BET_LOSS_LIMITS: [u64; 5] = [0, 10, 50, 100, 500];
+const AUTO_BET_ROUND_LIMITS: [u32; 5] = [0, 10, 50, 100, 1000];
+
+#[derive(Clone, Copy, PartialEq)]
+enum AutoBetStrategy {
+    /// Same stake and side every round.
+    Flat,
+    /// Double the stake after a loss, back to the base bet after a win.
+    Martingale,
+    /// Double the stake after a win, back to the base bet after a loss.
+    ReverseMartingale,
+    /// Switch sides every round.
+    Alternate,
+    /// Bet on whichever side won last.
+    FollowWinner,
+}
+
+impl AutoBetStrategy {
+    const ALL: [AutoBetStrategy; 5] = [
+        AutoBetStrategy::Flat,
+        AutoBetStrategy::Martingale,
+        AutoBetStrategy::ReverseMartingale,
+        AutoBetStrategy::Alternate,
+        AutoBetStrategy::FollowWinner,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            AutoBetStrategy::Flat => "flat",
+            AutoBetStrategy::Martingale => "martingale",
+            AutoBetStrategy::ReverseMartingale => "reverse martingale",
+            AutoBetStrategy::Alternate => "alternate",
+            AutoBetStrategy::FollowWinner => "follow winner",
+        }
+    }
+}
+
+/// Settings and progress of the auto-bet panel. Limits of 0 are off.
+#[derive(Resource)]
+struct AutoBet {
+    running: bool,
+    strategy: AutoBetStrategy,
+    side: Side,
+    profit_target: u64,
+    loss_limit: u64,
+    round_limit: u32,
+    base_bet: u64,
+    stake: u64,
+    start_score: u64,
+    rounds: u32,
+    awaiting_result: bool,
+    timer: Timer,
+    result_timeout: Timer,
+    status: String,
+}
+
+impl Default for AutoBet {
+    fn default() -> Self {
+        Self {
+            running: false,
+            strategy: AutoBetStrategy::Flat,
+            side: Side::Left,
+            profit_target: 0,
+            loss_limit: 0,
+            round_limit: 0,
+            base_bet: 1,
+            stake: 1,
+            start_score: 0,
+            rounds: 0,
+            awaiting_result: false,
+            timer: Timer::from_seconds(AUTO_BET_DELAY, TimerMode::Once),
+            result_timeout: Timer::from_seconds(AUTO_BET_TIMEOUT, TimerMode::Once),
+            status: "off".into(),
+        }
+    }
+}
+
+impl AutoBet {
+    fn start(&mut self, score: u64, bet: u64) {
+        self.running = true;
+        self.base_bet = bet;
+        self.stake = bet;
+        self.start_score = score;
+        self.rounds = 0;
+        self.awaiting_result = false;
+        self.timer.reset();
+    }
+
+    fn stop(&mut self, reason: impl Into<String>) {
+        self.running = false;
+        self.awaiting_result = false;
+        self.status = reason.into();
+    }
+
+    /// Picks the next stake and side from the result of the last round and stops once a limit
+    /// is hit.
+    fn settle(&mut self, result: &BetResultMessage, score: u64) {
+        self.awaiting_result = false;
+        self.rounds += 1;
+        let won = result.settlement == Settlement::Won;
+        self.stake = match (self.strategy, won) {
+            (AutoBetStrategy::Martingale, false) | (AutoBetStrategy::ReverseMartingale, true) => {
+                self.stake.saturating_mul(2)
+            }
+            _ => self.base_bet,
+        };
+        match self.strategy {
+            AutoBetStrategy::Alternate => {
+                self.side = match self.side {
+                    Side::Left => Side::Right,
+                    Side::Right => Side::Left,
+                }
+            }
+            AutoBetStrategy::FollowWinner => {
+                if let SettledRound::Gamba { outcome, .. } = &result.round {
+                    self.side = outcome.side.clone();
+                }
+            }
+            _ => (),
+        }
+
+        if result.settlement == Settlement::Bankrupt {
+            self.stop("stopped: went broke");
+        } else if self.profit_target > 0 && score >= self.start_score + self.profit_target {
+            self.stop("stopped: profit target reached");
+        } else if self.loss_limit > 0 && score + self.loss_limit <= self.start_score {
+            self.stop("stopped: loss limit reached");
+        } else if self.round_limit > 0 && self.rounds >= self.round_limit {
+            self.stop(format!("stopped: {} rounds played", self.rounds));
+        }
+    }
+}
+
+fn limit_name(limit: u64) -> String {
+    match limit {
+        0 => "off".into(),
+        _ => limit.to_string(),
+    }
+}
+
+#[derive(Component)]
+enum AutoBetDisplay {
+    Running,
+    Strategy,
+    Side,
+    ProfitTarget,
+    LossLimit,
+    RoundLimit,
+    Status,
+}
+
 fn setup_gamba(mut commands: Commands) {
     commands.spawn((
         Node {
@@ -270,6 +476,85 @@ fn setup_gamba(mut commands: Commands) {
             TextColor(Color::srgb_u8(29, 29, 29)),
         )],
     ));
+
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.),
+            padding: UiRect::all(Val::Px(8.)),
+            width: Val::Px(240.),
+            position_type: PositionType::Absolute,
+            bottom: Val::Percent(0.),
+            left: Val::Percent(0.),
+            ..default()
+        },
+        BackgroundColor(Color::srgba_u8(29, 29, 29, 160)),
+        OnGambaScreen,
+        children![
+            auto_bet_button(
+                ButtonAction::ToggleAutoBet,
+                "auto-bet: ",
+                AutoBetDisplay::Running
+            ),
+            auto_bet_button(
+                ButtonAction::CycleAutoBetStrategy,
+                "strategy: ",
+                AutoBetDisplay::Strategy
+            ),
+            auto_bet_button(
+                ButtonAction::CycleAutoBetSide,
+                "side: ",
+                AutoBetDisplay::Side
+            ),
+            auto_bet_button(
+                ButtonAction::CycleProfitTarget,
+                "stop at profit: $",
+                AutoBetDisplay::ProfitTarget
+            ),
+            auto_bet_button(
+                ButtonAction::CycleLossLimit,
+                "stop at loss: $",
+                AutoBetDisplay::LossLimit
+            ),
+            auto_bet_button(
+                ButtonAction::CycleRoundLimit,
+                "stop after rounds: ",
+                AutoBetDisplay::RoundLimit
+            ),
+            (
+                Text::default(),
+                TextFont {
+                    font_size: 12.,
+                    ..default()
+                },
+                children![(TextSpan::default(), AutoBetDisplay::Status)],
+            ),
+        ],
+    ));
+}
+
+fn auto_bet_button(
+    action: ButtonAction,
+    label: &str,
+    display: AutoBetDisplay,
+) -> impl Bundle + use<> {
+    (
+        Button,
+        action,
+        Node {
+            padding: UiRect::all(Val::Px(4.)),
+            ..default()
+        },
+        BackgroundColor(Color::srgb_u8(69, 69, 69)),
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size: 14.,
+                ..default()
+            },
+            children![(TextSpan::default(), display)],
+        )],
+    )
 }
 
 fn update_displays(
@@ -292,41 +577,180 @@ fn update_displays(
 #[derive(Component)]
 enum ButtonAction {
     Cake,
-    Gamba,
+    Casino,
     IncreaseBet,
     DecreaseBet,
     BetLeft,
     BetRight,
+    ToggleAutoBet,
+    CycleAutoBetStrategy,
+    CycleAutoBetSide,
+    CycleProfitTarget,
+    CycleLossLimit,
+    CycleRoundLimit,
 }
 
 fn handle_buttons(
-    interaction_query: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
+    mut commands: Commands,
+    interaction_query: Query<
+        (
+            Entity,
+            &Interaction,
+            &ButtonAction,
+            &BackgroundColor,
+            Has<Tween>,
+        ),
+        Changed<Interaction>,
+    >,
     mut game_state: ResMut<NextState<GameState>>,
     mut bet_change_message_writer: MessageWriter<BetChangeMessage>,
     mut bet_message_writer: MessageWriter<BetMessage>,
+    mut auto_bet: ResMut<AutoBet>,
+    score: Res<Score>,
+    bet: Res<Bet>,
+    round_state: Option<Res<State<GambaRoundState>>>,
+    safeguards: Res<SafeguardSettings>,
 ) {
-    for (interaction, button_action) in interaction_query {
+    let betting = betting_open(round_state);
+    for (entity, interaction, button_action, background, tweening) in interaction_query {
         if *interaction == Interaction::Pressed {
+            if !tweening {
+                commands.entity(entity).insert(
+                    Tween::new(
+                        TweenTarget::BackgroundColor {
+                            start: background.0,
+                            end: background.0.lighter(0.2),
+                        },
+                        0.1,
+                        EaseFunction::QuadraticOut,
+                    )
+                    .with_yoyo(),
+                );
+            }
             match button_action {
                 ButtonAction::Cake => {
                     game_state.set(GameState::Cake);
                 }
-                ButtonAction::Gamba => {
-                    game_state.set(GameState::Gamba);
+                ButtonAction::Casino if !gamba_locked(&safeguards) => {
+                    game_state.set(GameState::Casino);
                 }
+                ButtonAction::Casino => (),
                 ButtonAction::IncreaseBet => {
                     bet_change_message_writer.write(BetChangeMessage(BetChange::Increase));
                 }
                 ButtonAction::DecreaseBet => {
                     bet_change_message_writer.write(BetChangeMessage(BetChange::Decrease));
                 }
-                ButtonAction::BetLeft => {
-                    bet_message_writer.write(BetMessage(Side::Left));
+                ButtonAction::BetLeft if betting => {
+                    bet_message_writer.write(BetMessage(BetKind::Side(Side::Left)));
+                }
+                ButtonAction::BetRight if betting => {
+                    bet_message_writer.write(BetMessage(BetKind::Side(Side::Right)));
+                }
+                ButtonAction::BetLeft | ButtonAction::BetRight => (),
+                ButtonAction::ToggleAutoBet => {
+                    if auto_bet.running {
+                        auto_bet.stop("stopped");
+                    } else {
+                        auto_bet.start(score.0, bet.0);
+                    }
                 }
-                ButtonAction::BetRight => {
-                    bet_message_writer.write(BetMessage(Side::Right));
+                ButtonAction::CycleAutoBetStrategy => {
+                    auto_bet.strategy = cycle(&AutoBetStrategy::ALL, auto_bet.strategy);
+                }
+                ButtonAction::CycleAutoBetSide => {
+                    auto_bet.side = match auto_bet.side {
+                        Side::Left => Side::Right,
+                        Side::Right => Side::Left,
+                    };
+                }
+                ButtonAction::CycleProfitTarget => {
+                    auto_bet.profit_target =
+                        cycle(&AUTO_BET_PROFIT_TARGETS, auto_bet.profit_target);
+                }
+                ButtonAction::CycleLossLimit => {
+                    auto_bet.loss_limit = cycle(&AUTO_BET_LOSS_LIMITS, auto_bet.loss_limit);
+                }
+                ButtonAction::CycleRoundLimit => {
+                    auto_bet.round_limit = cycle(&AUTO_BET_ROUND_LIMITS, auto_bet.round_limit);
                 }
             }
         }
     }
 }
+
+/// Places the next auto-bet round once the previous one is settled and the camera has caught up
+/// with the new sugar cane.
+fn run_auto_bet(
+    time: Res<Time>,
+    mut auto_bet: ResMut<AutoBet>,
+    score: Res<Score>,
+    mut result_messages: MessageReader<BetResultMessage>,
+    mut rejected_messages: MessageReader<BetRejectedMessage>,
+    moving_camera: Query<(), (With<Camera>, With<Tween>)>,
+    round_state: Option<Res<State<GambaRoundState>>>,
+    mut bet_change_message_writer: MessageWriter<BetChangeMessage>,
+    mut bet_message_writer: MessageWriter<BetMessage>,
+) {
+    for result in result_messages.read() {
+        if auto_bet.running && auto_bet.awaiting_result {
+            auto_bet.settle(result, score.0);
+            auto_bet.timer.reset();
+        }
+    }
+    for rejected in rejected_messages.read() {
+        if auto_bet.running && auto_bet.awaiting_result {
+            auto_bet.stop(format!("stopped: {}", rejected.reason));
+        }
+    }
+    if auto_bet.running && auto_bet.awaiting_result {
+        auto_bet.result_timeout.tick(time.delta());
+        if auto_bet.result_timeout.is_finished() {
+            auto_bet.stop("stopped: bet was never settled");
+        }
+        return;
+    }
+    if !auto_bet.running || !moving_camera.is_empty() || !betting_open(round_state) {
+        return;
+    }
+    auto_bet.timer.tick(time.delta());
+    if auto_bet.timer.is_finished() {
+        auto_bet.awaiting_result = true;
+        auto_bet.result_timeout.reset();
+        bet_change_message_writer.write(BetChangeMessage(BetChange::Set(auto_bet.stake)));
+        bet_message_writer.write(BetMessage(BetKind::Side(auto_bet.side.clone())));
+    }
+}
+
+fn stop_auto_bet(mut auto_bet: ResMut<AutoBet>) {
+    if auto_bet.running {
+        auto_bet.stop("stopped");
+    }
+}
+
+fn update_auto_bet_panel(
+    auto_bet: Res<AutoBet>,
+    score: Res<Score>,
+    mut spans: Query<(&mut TextSpan, &AutoBetDisplay)>,
+    spawned: Query<(), Added<AutoBetDisplay>>,
+) {
+    if !auto_bet.is_changed() && !score.is_changed() && spawned.is_empty() {
+        return;
+    }
+    for (mut span, display) in &mut spans {
+        **span = match display {
+            AutoBetDisplay::Running => if auto_bet.running { "on" } else { "off" }.into(),
+            AutoBetDisplay::Strategy => auto_bet.strategy.name().into(),
+            AutoBetDisplay::Side => side_name(&auto_bet.side).into(),
+            AutoBetDisplay::ProfitTarget => limit_name(auto_bet.profit_target),
+            AutoBetDisplay::LossLimit => limit_name(auto_bet.loss_limit),
+            AutoBetDisplay::RoundLimit => limit_name(auto_bet.round_limit.into()),
+            AutoBetDisplay::Status if auto_bet.running => format!(
+                "round {}, {:+} $",
+                auto_bet.rounds,
+                score.0 as i64 - auto_bet.start_score as i64
+            ),
+            AutoBetDisplay::Status => auto_bet.status.clone(),
+        };
+    }
+}
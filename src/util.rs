@@ -6,23 +6,22 @@ pub fn despawn_all<C: Component>(mut commands: Commands, to_despawn: Query<Entit
     }
 }
 
-pub trait Animation {
-    fn index() -> usize;
-    fn size() -> usize;
-    fn timer(&mut self) -> &mut Timer;
+/// The option after `current`, wrapping around.
+pub fn cycle<T: Copy + PartialEq>(options: &[T], current: T) -> T {
+    let index = options.iter().position(|o| *o == current).unwrap_or(0);
+    options[(index + 1) % options.len()]
 }
 
-pub fn animate<A: Component<Mutability = bevy::ecs::component::Mutable> + Animation>(
-    time: Res<Time>,
-    mut query: Query<(&mut A, &mut Sprite)>,
-) {
-    for (mut animation, mut sprite) in &mut query {
-        let timer = animation.timer();
-        timer.tick(time.delta());
-        if timer.is_finished()
-            && let Some(atlas) = &mut sprite.texture_atlas
-        {
-            atlas.index = (atlas.index + 1 - A::index()) % A::size() + A::index();
-        }
-    }
+/// Wall clock time in seconds since the Unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Wall clock time in seconds since the Unix epoch.
+#[cfg(target_arch = "wasm32")]
+pub fn unix_time() -> u64 {
+    (js_sys::Date::now() / 1000.) as u64
 }
@@ -0,0 +1,261 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_light_2d::prelude::*;
+
+use crate::{
+    GameState,
+    assets::Assets,
+    cake::{ExtinguishFlameMessage, Flame, LIT_AMBIENT, handle_extinguish_messages},
+    particles::ParticleEmitter,
+    replay::live_input,
+    save::Stats,
+    trick_candles::Relit,
+    tween::{Tween, TweenCompleted, TweenTarget},
+    util::despawn_all,
+};
+
+/// Blowing out every candle quickly enough makes a wish: the lights go down and the cake
+/// celebrates. Flames are blown out by swiping fast across them, or by moving over them while
+/// holding [`BLOW_KEY`].
+pub struct WishPlugin;
+
+impl Plugin for WishPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Blowing>()
+            .insert_resource(AmbientTarget(LIT_AMBIENT))
+            .add_message::<WishMessage>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        blow_flames.before(handle_extinguish_messages),
+                        watch_candles,
+                    )
+                        .chain()
+                        .run_if(live_input),
+                    (make_wish, relight_room, despawn_celebration),
+                    fade_ambient_light,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Cake)),
+            )
+            .add_systems(
+                OnExit(GameState::Cake),
+                (despawn_all::<OnCakeScreen>, reset_ambient_light),
+            );
+    }
+}
+
+#[derive(Component)]
+struct OnCakeScreen;
+
+/// Seconds between the first and the last flame going out for it to count as a wish.
+const WISH_WINDOW_SECS: f32 = 5.;
+/// Pointer speed, in world units per second, fast enough to blow out the flames it passes.
+const BLOW_SPEED: f32 = 1500.;
+/// How close to a flame the pointer has to pass to blow it out.
+const BLOW_RADIUS: f32 = 30.;
+const BLOW_KEY: KeyCode = KeyCode::Space;
+
+const WISH_AMBIENT: f32 = 0.25;
+/// How quickly the ambient light follows its target, see [`StableInterpolate::smooth_nudge`].
+const AMBIENT_DECAY_RATE: f32 = 3.;
+
+/// Sent when every flame on the cake went out within [`WISH_WINDOW_SECS`].
+#[derive(Message)]
+pub struct WishMessage;
+
+/// How blowing out the current set of candles is going. Only tracked live, replays are told about
+/// wishes by [`WishMessage`].
+#[derive(Resource, Default)]
+struct Blowing {
+    /// Whether there are candles left to blow out.
+    lit: bool,
+    /// When the first of them went out.
+    first_out: Option<f32>,
+}
+
+/// Ambient light brightness the room is fading towards.
+#[derive(Resource)]
+struct AmbientTarget(f32);
+
+#[derive(Component)]
+struct CelebrationPart;
+
+/// Puts out every flame the pointer passes over while it moves fast or [`BLOW_KEY`] is held.
+fn blow_flames(
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    touches: Res<Touches>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    flames: Query<(&Flame, &GlobalTransform)>,
+    mut last_position: Local<Option<Vec2>>,
+    mut extinguish_messages: MessageWriter<ExtinguishFlameMessage>,
+) {
+    let (camera, camera_transform) = *camera;
+    let Some(position) = window
+        .cursor_position()
+        .or_else(|| touches.iter().next().map(|touch| touch.position()))
+        .and_then(|position| camera.viewport_to_world_2d(camera_transform, position).ok())
+    else {
+        *last_position = None;
+        return;
+    };
+    let Some(from) = last_position.replace(position) else {
+        return;
+    };
+    let speed = from.distance(position) / time.delta_secs().max(f32::EPSILON);
+    if speed < BLOW_SPEED && !keys.pressed(BLOW_KEY) {
+        return;
+    }
+
+    let swipe = position - from;
+    for (flame, transform) in &flames {
+        let flame_position = transform.translation().truncate();
+        let along = ((flame_position - from).dot(swipe) / swipe.length_squared().max(f32::EPSILON))
+            .clamp(0., 1.);
+        if flame_position.distance(from + swipe * along) <= BLOW_RADIUS {
+            extinguish_messages.write(ExtinguishFlameMessage { slot: flame.slot });
+        }
+    }
+}
+
+/// Sends a [`WishMessage`] once the last flame of a fresh set of candles goes out in time.
+fn watch_candles(
+    time: Res<Time>,
+    mut blowing: ResMut<Blowing>,
+    mut extinguish_messages: MessageReader<ExtinguishFlameMessage>,
+    lit: Query<(), (Added<Flame>, Without<Relit>)>,
+    flames: Query<(), With<Flame>>,
+    mut wish_messages: MessageWriter<WishMessage>,
+) {
+    if !lit.is_empty() {
+        *blowing = Blowing {
+            lit: true,
+            first_out: None,
+        };
+    }
+    if extinguish_messages.read().count() > 0 && blowing.first_out.is_none() {
+        blowing.first_out = Some(time.elapsed_secs());
+    }
+    if !blowing.lit || !flames.is_empty() {
+        return;
+    }
+
+    blowing.lit = false;
+    if blowing
+        .first_out
+        .is_some_and(|first_out| time.elapsed_secs() - first_out <= WISH_WINDOW_SECS)
+    {
+        wish_messages.write(WishMessage);
+    }
+}
+
+fn make_wish(
+    mut commands: Commands,
+    mut wish_messages: MessageReader<WishMessage>,
+    mut ambient: ResMut<AmbientTarget>,
+    mut stats: ResMut<Stats>,
+    assets: Res<Assets>,
+) {
+    for _ in wish_messages.read() {
+        stats.wishes_made += 1;
+        ambient.0 = WISH_AMBIENT;
+        commands.spawn(AudioPlayer(assets.win3.clone()));
+        spawn_wish_prompt(&mut commands);
+        commands.spawn((
+            ParticleEmitter::confetti(),
+            Transform::from_xyz(0., 300., 10.),
+            OnCakeScreen,
+        ));
+    }
+}
+
+fn spawn_wish_prompt(commands: &mut Commands) {
+    commands.spawn((
+        Node {
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        UiTransform::from_scale(Vec2::ZERO),
+        Tween::new(
+            TweenTarget::UiScale {
+                start: Vec2::ZERO,
+                end: Vec2::ONE,
+            },
+            0.6,
+            EaseFunction::BounceOut,
+        )
+        .then(
+            TweenTarget::UiScale {
+                start: Vec2::ONE,
+                end: Vec2::ONE,
+            },
+            2.5,
+            EaseFunction::Linear,
+        )
+        .then(
+            TweenTarget::UiScale {
+                start: Vec2::ONE,
+                end: Vec2::ZERO,
+            },
+            0.4,
+            EaseFunction::QuadraticIn,
+        ),
+        CelebrationPart,
+        OnCakeScreen,
+        children![(
+            Text::new("make a wish"),
+            TextFont {
+                font_size: 96.,
+                ..default()
+            },
+            TextColor(Color::srgb_u8(243, 207, 198)),
+        )],
+    ));
+}
+
+/// Brings the lights back up once a fresh set of candles is lit.
+fn relight_room(
+    lit: Query<(), (Added<Flame>, Without<Relit>)>,
+    mut ambient: ResMut<AmbientTarget>,
+) {
+    if !lit.is_empty() {
+        ambient.0 = LIT_AMBIENT;
+    }
+}
+
+fn despawn_celebration(
+    mut commands: Commands,
+    mut tween_completed_messages: MessageReader<TweenCompleted>,
+    celebration: Query<(), With<CelebrationPart>>,
+) {
+    for msg in tween_completed_messages.read() {
+        if celebration.contains(msg.entity) {
+            commands.entity(msg.entity).despawn();
+        }
+    }
+}
+
+fn fade_ambient_light(
+    time: Res<Time>,
+    ambient: Res<AmbientTarget>,
+    mut light: Single<&mut Light2d>,
+) {
+    if light.ambient_light.brightness == ambient.0 {
+        return;
+    }
+    light
+        .ambient_light
+        .brightness
+        .smooth_nudge(&ambient.0, AMBIENT_DECAY_RATE, time.delta_secs());
+}
+
+fn reset_ambient_light(mut ambient: ResMut<AmbientTarget>, mut light: Single<&mut Light2d>) {
+    ambient.0 = LIT_AMBIENT;
+    light.ambient_light.brightness = LIT_AMBIENT;
+}